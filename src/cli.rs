@@ -0,0 +1,100 @@
+use std::ffi::OsString;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+pub static USAGE: &str = "\
+Usage: jaffacode [OPTIONS] [[+LINE] FILE]...
+
+Open every FILE in its own pane. A FILE of `-` reads the text from stdin,
+a FILE that does not exist yet is opened as an empty buffer.
+
+Options:
+  +LINE       put the cursor on LINE of the next FILE
+  -h, --help  print this help
+  --          treat every following argument as a FILE";
+
+#[derive(Debug)]
+pub enum Source {
+    Path(PathBuf),
+    Stdin,
+}
+
+#[derive(Debug)]
+pub struct FileArg {
+    pub source: Source,
+    pub line: Option<usize>,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Help,
+    Open(Vec<FileArg>),
+}
+
+pub fn parse(args: impl IntoIterator<Item = OsString>) -> Result<Command, String> {
+    let mut files = Vec::new();
+    let mut line = None;
+    let mut only_files = false;
+    for arg in args {
+        let source = match arg.to_str() {
+            Some(_) if only_files => Source::Path(arg.into()),
+            Some("--") => {
+                only_files = true;
+                continue;
+            }
+            Some("-h" | "--help") => return Ok(Command::Help),
+            Some("-") => {
+                if files
+                    .iter()
+                    .any(|file: &FileArg| matches!(file.source, Source::Stdin))
+                {
+                    return Err("stdin can only be opened once".to_string());
+                }
+                Source::Stdin
+            }
+            Some(arg) if arg.starts_with('+') => {
+                let n = arg[1..]
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid line number: {arg}"))?;
+                line = Some(n.saturating_sub(1));
+                continue;
+            }
+            Some(arg) if arg.starts_with('-') => return Err(format!("unknown option: {arg}")),
+            _ => Source::Path(arg.into()),
+        };
+        files.push(FileArg {
+            source,
+            line: line.take(),
+        });
+    }
+    if line.is_some() {
+        return Err("missing FILE after +LINE".to_string());
+    }
+    Ok(Command::Open(files))
+}
+
+impl FileArg {
+    pub fn read(&self) -> Result<String, String> {
+        match &self.source {
+            Source::Path(path) => match std::fs::read_to_string(path) {
+                Ok(text) => Ok(text),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+                Err(err) => Err(format!("{}: {}", path.display(), err)),
+            },
+            Source::Stdin => {
+                let mut text = String::new();
+                io::stdin()
+                    .read_to_string(&mut text)
+                    .map_err(|err| format!("stdin: {err}"))?;
+                Ok(text)
+            }
+        }
+    }
+
+    pub fn path(&self) -> Option<PathBuf> {
+        match &self.source {
+            Source::Path(path) => Some(path.clone()),
+            Source::Stdin => None,
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+pub trait Clipboard {
+    fn copy(&mut self, text: &str, terminal: &mut dyn Write) -> io::Result<()>;
+}
+
+// NOTE: the terminal forwards the text to the host clipboard, which works over SSH; tmux needs
+//       `set -g set-clipboard on` to let it through
+#[derive(Debug, Default)]
+pub struct Osc52;
+
+impl Clipboard for Osc52 {
+    fn copy(&mut self, text: &str, terminal: &mut dyn Write) -> io::Result<()> {
+        write!(terminal, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+        terminal.flush()
+    }
+}
+
+#[derive(Debug)]
+pub struct External {
+    program: String,
+    args: Vec<String>,
+}
+
+impl External {
+    pub fn new(command_line: &str) -> Option<Self> {
+        let mut words = command_line.split_whitespace().map(str::to_string);
+        Some(Self {
+            program: words.next()?,
+            args: words.collect(),
+        })
+    }
+}
+
+impl Clipboard for External {
+    fn copy(&mut self, text: &str, _terminal: &mut dyn Write) -> io::Result<()> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let res = child.stdin.take().unwrap().write_all(text.as_bytes());
+        let status = child.wait()?;
+        res?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "{} failed: {}",
+                self.program, status
+            )));
+        }
+        Ok(())
+    }
+}
+
+// NOTE: `JAFFACODE_CLIPBOARD` can be `osc52`, `none` or a command reading the text on stdin,
+//       otherwise OSC 52 is used over SSH and `wl-copy` or `xclip` on a local display
+pub fn from_env() -> Option<Box<dyn Clipboard>> {
+    match std::env::var("JAFFACODE_CLIPBOARD").ok().as_deref() {
+        Some("none") => return None,
+        Some("osc52") => return Some(Box::new(Osc52)),
+        Some(command_line) => {
+            if let Some(external) = External::new(command_line) {
+                return Some(Box::new(external));
+            }
+        }
+        None => {}
+    }
+    let is_set = |var| std::env::var_os(var).is_some_and(|value| !value.is_empty());
+    if is_set("SSH_CONNECTION") || is_set("SSH_TTY") {
+        return Some(Box::new(Osc52));
+    }
+    let candidates = [
+        ("WAYLAND_DISPLAY", "wl-copy"),
+        ("DISPLAY", "xclip -selection clipboard"),
+    ];
+    for (display, command_line) in candidates {
+        if is_set(display) && in_path(command_line.split(' ').next().unwrap()) {
+            return External::new(command_line).map(|external| Box::new(external) as _);
+        }
+    }
+    Some(Box::new(Osc52))
+}
+
+fn in_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -1,18 +1,48 @@
 // TODO
-// - clipboard history: copy and cutting push to a stack, pasting pop from that stack
 // - opening without argument opens the whole project with a view of everything simplified
-// - dd on a line that has an indented block right after should dedent that block
 // - consider splitting blocks on vertical space
 
+mod action;
 mod buffer;
 #[cfg(feature = "crossterm")]
 mod crossterm;
+mod cursors;
+mod graphemes;
+mod highlight;
+mod history;
+mod indentation;
+mod keymap;
+mod kill_ring;
+mod navigation;
+mod replace;
+mod rust_lexer;
+mod search;
+mod selection;
+#[cfg(test)]
+mod tests;
+mod vim;
 mod vlines;
 mod window;
+mod wrap;
 
+pub use self::action::Action;
 use self::buffer::*;
+use self::graphemes::*;
+use self::highlight::*;
+pub use self::highlight::{HighlightState, Highlighter, TokenKind, highlighter_for};
+pub use self::history::fnv1a;
+use self::history::*;
+pub use self::indentation::Indentation;
+pub use self::keymap::{KeyCode, Keymap, Keystroke, Resolved, format_keys};
+pub use self::kill_ring::KillRing;
+use self::replace::*;
+pub use self::rust_lexer::RustHighlighter;
+pub use self::search::SearchOptions;
+use self::search::*;
+pub use self::vim::Vim;
 use self::vlines::*;
 use self::window::*;
+pub use self::wrap::WrapMode;
 
 use ropey::*;
 use slotmap::*;
@@ -20,7 +50,18 @@ use slotmap::*;
 const INDENT: usize = 4;
 const WRAP_AT: usize = 40;
 const MIN_WRAP_AT: usize = 12;
-static HSPACES: &str = "                                                                                                                                                                                                        ";
+// NOTE: past this many edits made at once, the lines of the buffers they are made in are laid out
+//       again afterwards instead of being adjusted after each edit
+const RELAYOUT_EDITS: usize = 64;
+const HISTORY_MAGIC: &[u8] = b"JaffaCode undo 2\n";
+// NOTE: long enough for the widest wrap
+const MAX_WRAP_AT: usize = 1000;
+// NOTE: the width lines are laid out at when wrapping is off, none is ever split there
+const NO_WRAP: usize = usize::MAX / 2;
+static HSPACES: &str = match std::str::from_utf8(&[b' '; MAX_WRAP_AT + 24]) {
+    Ok(spaces) => spaces,
+    Err(_) => unreachable!(),
+};
 static VSPACES: &str = "\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n";
 
 new_key_type! {
@@ -37,15 +78,64 @@ pub struct Editor {
     #[debug(skip)]
     buffers: BufferMap,
     window: Window,
+    #[debug(skip)]
+    text: Rope,
+    // NOTE: the edits reported by the buffers and not yet written to the text, then the ones
+    //       written to it and not yet recorded in the history
+    #[debug(skip)]
+    pending: Vec<BufferEdit>,
+    #[debug(skip)]
+    edits: Vec<Edit>,
+    #[debug(skip)]
+    history: History,
+    // NOTE: the focus changes asked for, like the pages of a browser
+    window_back: Vec<WindowState>,
+    window_forward: Vec<WindowState>,
+    anchor: Option<Point>,
+    cursors: Vec<Point>,
+    // NOTE: a mark set on purpose keeps the selection while the cursor moves
+    mark: bool,
+    last_paste: Option<(Point, Point, usize)>,
+    search: Option<Search>,
+    replace: Option<Replace>,
+    #[debug(skip)]
+    highlight: Option<Highlight>,
+    indentation: Indentation,
+    // NOTE: by line of the text, the leading whitespace of the file collapsing would not give
+    //       back, until the line is edited, empty when there is none
+    file_indents: Vec<Option<Box<str>>>,
+    wrap: WrapMode,
+    wrap_at: usize,
+    word_wrap: bool,
+    scroll_x: usize,
     pane_width: u16,
     pane_height: u16,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Point {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowState {
+    focus: Option<(usize, usize)>,
+    cursor: Point,
+    cur_y: u16,
+}
+
 #[derive(derive_more::Debug)]
 pub struct DisplayLine<'r> {
     pub slice: RopeSlice<'r>,
     pub indent: &'static str,
-    pub continuation: bool,
+    // NOTE: only on the first visual line of a line of the text
+    pub line_number: Option<usize>,
+    pub selected: Option<std::ops::Range<usize>>,
+    pub highlights: Vec<std::ops::Range<usize>>,
+    pub tokens: Vec<(std::ops::Range<usize>, TokenKind)>,
+    // NOTE: the chars under the extra cursors, the newline when one is at the end of the line
+    pub cursors: Vec<std::ops::Range<usize>>,
 }
 
 #[derive(derive_more::Debug)]
@@ -56,16 +146,64 @@ pub struct Location<'r> {
 
 impl Editor {
     pub fn new(initial_text: &str) -> Self {
-        let mut rope = Rope::from_str(initial_text);
-        let len_chars = rope.len_chars();
-        if rope.char(len_chars - 1) != '\n' {
-            rope.insert_char(len_chars, '\n');
+        let indentation = Indentation::detect(initial_text);
+        let mut text = Rope::from_str(&indentation.expand(initial_text));
+        let len_chars = text.len_chars();
+        if len_chars == 0 || text.char(len_chars - 1) != '\n' {
+            text.insert_char(len_chars, '\n');
+        }
+
+        let mut file_indents = indentation.file_indents(initial_text);
+        if !file_indents.is_empty() {
+            file_indents.resize(text.len_lines(), None);
+        }
+        let (ropes, vlines, buffers) = Self::load(&text, WRAP_AT, indentation.width, false);
+
+        let window = Window::new(
+            VLineCursor::new(&vlines, vlines.first()),
+            VLineCursor::null(),
+            0,
+        );
+
+        Self {
+            ropes,
+            vlines,
+            buffers,
+            window,
+            text,
+            pending: Vec::new(),
+            edits: Vec::new(),
+            history: Default::default(),
+            window_back: Vec::new(),
+            window_forward: Vec::new(),
+            anchor: None,
+            cursors: Vec::new(),
+            mark: false,
+            last_paste: None,
+            search: None,
+            replace: None,
+            highlight: None,
+            indentation,
+            file_indents,
+            wrap: WrapMode::default(),
+            wrap_at: WRAP_AT,
+            word_wrap: false,
+            scroll_x: 0,
+            pane_width: 0,
+            pane_height: 0,
         }
+    }
 
+    fn load(
+        text: &Rope,
+        wrap_at: usize,
+        indent_width: usize,
+        word_wrap: bool,
+    ) -> (RopeMap, VLines, BufferMap) {
         let mut ropes = RopeMap::with_key();
-        let rope_key = ropes.insert(rope);
+        let rope_key = ropes.insert(text.clone());
 
-        let vlines = VLines::new(&ropes, rope_key);
+        let vlines = VLines::new(&ropes, rope_key, wrap_at, indent_width, word_wrap);
 
         let mut buffers = BufferMap::new();
         buffers.insert(
@@ -74,60 +212,387 @@ impl Editor {
                 rope_key,
                 VLineCursor::new(&vlines, vlines.first()),
                 VLineCursor::null(),
-                WRAP_AT,
+                0,
+                wrap_at,
                 0,
             ),
         );
 
-        let window = Window::new(
-            &buffers,
-            &vlines,
-            VLineCursor::new(&vlines, vlines.first()),
-            VLineCursor::null(),
+        (ropes, vlines, buffers)
+    }
+
+    fn reload(&mut self, text: Rope, state: &WindowState) {
+        (self.ropes, self.vlines, self.buffers) =
+            Self::load(&text, self.wrap_at, self.indentation.width, self.word_wrap);
+        if let Some(edit) = Edit::diff(&self.text, &text) {
+            self.invalidate_highlight(edit.char_idx);
+            self.shift_window_history(&edit);
+        }
+        self.text = text;
+        self.reset_window(state);
+    }
+
+    // NOTE: the lines the window was on may be gone after an edit that is not typing, and the
+    //       extra cursors only follow the edits made at all of them
+    fn reset_window(&mut self, state: &WindowState) {
+        self.cursors.clear();
+        self.whole_window();
+        self.restore_window_state(state);
+    }
+
+    fn record(&mut self, f: impl FnOnce(&mut Self) -> bool) -> bool {
+        self.anchor = None;
+        let before = self.window_state();
+        let changed = f(self);
+        self.sync_text();
+        let edits = std::mem::take(&mut self.edits);
+        if !edits.is_empty() {
+            let after = self.window_state();
+            self.history.record(edits, before, after);
+            self.refresh_search();
+        }
+        changed
+    }
+
+    // NOTE: called right after the buffers are edited, before a block can be split, the lines of
+    //       the text before the edited ones are still the same
+    fn sync_text(&mut self) {
+        for BufferEdit {
+            key,
+            line,
+            mut edit,
+        } in std::mem::take(&mut self.pending)
+        {
+            edit.char_idx += self.text.line_to_char(self.buffers[key].first_line + line);
+            self.invalidate_highlight(edit.char_idx);
+            self.shift_window_history(&edit);
+            if !self.file_indents.is_empty() {
+                let first = self.text.char_to_line(edit.char_idx);
+                let last = self
+                    .text
+                    .char_to_line(edit.char_idx + edit.removed.chars().count());
+                let edited = std::iter::repeat_n(None, edit.inserted.matches('\n').count() + 1);
+                self.file_indents.splice(first..=last, edited);
+            }
+            edit.apply(&mut self.text);
+            let lines = edit.inserted.matches('\n').count() as isize
+                - edit.removed.matches('\n').count() as isize;
+            if lines != 0 {
+                self.shift_first_lines(key, lines);
+            }
+            self.edits.push(edit);
+        }
+    }
+
+    // NOTE: edits of the text made one after the other through the buffer of the lines each one
+    //       spans, the blocks are joined first when it spans several of them or leaves a line
+    //       less indented than its block, the others are kept as they are
+    fn edit_text(&mut self, edits: &[Edit]) {
+        let relayout = edits.len() > RELAYOUT_EDITS;
+        let mut stale = Vec::new();
+        for edit in edits {
+            let Some((key, head_line, range, text)) = self.locate_edit(edit, &mut stale) else {
+                continue;
+            };
+            if relayout {
+                self.buffers[key].edit_rope(&mut self.ropes, &mut self.pending, range, &text);
+                if !stale.contains(&key) {
+                    stale.push(key);
+                }
+                self.sync_text();
+                continue;
+            }
+            let head = self.line_cursor(head_line).unwrap();
+            if !range.is_empty() {
+                let cursor = vline_at_char(&self.vlines, &self.ropes, head, range.start);
+                self.buffers[key].remove(
+                    &mut self.vlines,
+                    &mut self.ropes,
+                    &mut self.pending,
+                    range.clone(),
+                    cursor,
+                );
+            }
+            if !text.is_empty() {
+                let cursor = vline_at_char(&self.vlines, &self.ropes, head, range.start);
+                self.buffers[key].insert(
+                    &mut self.vlines,
+                    &mut self.ropes,
+                    &mut self.pending,
+                    range.start,
+                    &text,
+                    cursor,
+                );
+            }
+            self.sync_text();
+        }
+        for key in stale {
+            self.relayout_buffer(key);
+        }
+    }
+
+    // NOTE: the buffer an edit of the text is made in, the line of the text its lines start at,
+    //       then the chars of the rope it replaces and with what. The buffers in `stale` have
+    //       their ropes edited but not their lines yet, which joining needs
+    fn locate_edit(
+        &mut self,
+        edit: &Edit,
+        stale: &mut Vec<BufferKey>,
+    ) -> Option<(BufferKey, usize, std::ops::Range<usize>, String)> {
+        let start = edit.char_idx;
+        let end = start + edit.removed.chars().count();
+        // NOTE: the text always ends with a newline, the empty line ropey gives after it is
+        //       edited with the one before
+        let last_line = self.line_count() - 1;
+        let mut first = self.text.char_to_line(start).min(last_line);
+        let last = self.text.char_to_line(end).min(last_line);
+        let mut new = String::new();
+        new.extend(
+            self.text
+                .slice(self.text.line_to_char(first)..start)
+                .chunks(),
+        );
+        new.push_str(&edit.inserted);
+        new.extend(
+            self.text
+                .slice(end..self.text.line_to_char(last + 1))
+                .chunks(),
         );
+        if new.is_empty() {
+            // NOTE: whole lines are removed, the one before keeps the buffer from being emptied
+            first -= 1;
+            new = self.text.line(first).to_string();
+        }
+        debug_assert!(new.ends_with('\n'), "missing newline at EOF");
 
-        Self {
-            ropes,
-            vlines,
-            buffers,
-            window,
-            pane_width: 0,
-            pane_height: 0,
+        let width = self.indentation.width;
+        let new_indent = new
+            .split_inclusive('\n')
+            .filter(|line| !is_blank(RopeSlice::from(*line)))
+            .map(|line| line.chars().take_while(|c| *c == ' ').count() / width * width)
+            .min();
+        let blocks = self
+            .first_lines()
+            .take_while(|(_, first_line)| *first_line <= last)
+            .collect::<Vec<_>>();
+        let i = blocks
+            .iter()
+            .rposition(|(_, first_line)| *first_line <= first)
+            .unwrap();
+        let (key, first_line) = blocks[i];
+        let indent = blocks[i..]
+            .iter()
+            .map(|(key, _)| self.buffers[*key].indent)
+            .chain(new_indent)
+            .min()
+            .unwrap();
+        if i + 1 < blocks.len() || indent < self.buffers[key].indent {
+            for key in stale.drain(..) {
+                self.relayout_buffer(key);
+            }
+            self.join_buffers(key, blocks[blocks.len() - 1].0, indent);
         }
+
+        let rope = &self.ropes[key];
+        let line_start = rope.line_to_char(first - first_line);
+        let old = rope
+            .slice(line_start..rope.line_to_char(last - first_line + 1))
+            .to_string();
+        let new = new
+            .split_inclusive('\n')
+            .map(|line| {
+                if is_blank(RopeSlice::from(line)) {
+                    line
+                } else {
+                    &line[indent..]
+                }
+            })
+            .collect::<String>();
+        // NOTE: the last newline stays, so a line removed whole is merged with the next one of
+        //       the buffer and never with the next buffer
+        let edit = Edit::between(&old[..old.len() - 1], &new[..new.len() - 1])?;
+        let char_idx = line_start + edit.char_idx;
+        let range = char_idx..char_idx + edit.removed.chars().count();
+        Some((key, first, range, edit.inserted))
+    }
+
+    // NOTE: the buffers from `first` to `last` become a single one with the given indentation,
+    //       which none of their lines is below
+    fn join_buffers(&mut self, first: BufferKey, last: BufferKey, indent: usize) {
+        let mut text = String::new();
+        let mut keys = vec![first];
+        loop {
+            let key = keys[keys.len() - 1];
+            let buffer = &self.buffers[key];
+            for line in self.ropes[key].lines() {
+                if !is_blank(line) {
+                    text.push_str(&HSPACES[..buffer.indent - indent]);
+                }
+                text.extend(line.chunks());
+            }
+            if key == last {
+                break;
+            }
+            keys.push(self.vlines[buffer.end].buffer_key);
+        }
+        let Buffer {
+            start, first_line, ..
+        } = self.buffers[first];
+        let end = self.buffers[last].end;
+        for key in keys.drain(1..) {
+            self.ropes.remove(key);
+            self.buffers.remove(key);
+        }
+        self.ropes[first] = Rope::from_str(&text);
+        let wrap_at = self.wrap_at.saturating_sub(indent).max(MIN_WRAP_AT);
+        self.buffers.insert(
+            first,
+            Buffer::new(first, start, end, first_line, wrap_at, indent),
+        );
+        self.relayout_buffer(first);
+    }
+
+    // NOTE: the lines of the buffer are laid out again from its rope, its first line gets a new
+    //       key which the buffer before it ends at
+    fn relayout_buffer(&mut self, key: BufferKey) {
+        let buffer = &self.buffers[key];
+        let head = self.vlines.replace(
+            &self.ropes,
+            buffer.start.head_key(),
+            buffer.end.head_key(),
+            key,
+            buffer.wrap_at,
+        );
+        let start = VLineCursor::new(&self.vlines, head);
+        if let Some(prev) = start.peek_prev_logical(&self.vlines) {
+            self.buffers[self.vlines[prev].buffer_key].end = start;
+        }
+        self.buffers[key].start = start;
+    }
+
+    // NOTE: the edits are made through the buffers like any other, the blocks they do not span
+    //       are kept
+    fn navigate_history(
+        &mut self,
+        f: impl FnOnce(&mut History, &mut Vec<Edit>) -> Option<WindowState>,
+    ) -> bool {
+        let mut edits = Vec::new();
+        let Some(state) = f(&mut self.history, &mut edits) else {
+            return false;
+        };
+        self.anchor = None;
+        self.edit_text(&edits);
+        self.edits.clear();
+        match self.history.indentation() {
+            Some(indentation) if indentation != self.indentation => {
+                self.change_indentation(indentation);
+                self.reload(self.text.clone(), &state);
+            }
+            _ => self.reset_window(&state),
+        }
+        self.refresh_search();
+        true
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.navigate_history(History::undo)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.navigate_history(History::redo)
+    }
+
+    pub fn undo_earlier(&mut self) -> bool {
+        self.navigate_history(History::earlier)
+    }
+
+    pub fn undo_later(&mut self) -> bool {
+        self.navigate_history(History::later)
+    }
+
+    pub fn undo_next_branch(&mut self) -> bool {
+        self.navigate_history(History::next_branch)
+    }
+
+    pub fn undo_travel(&mut self, secs: i64) -> bool {
+        self.navigate_history(|history, edits| history.travel(secs, edits))
+    }
+
+    pub fn content_hash(&self) -> u64 {
+        fnv1a(self.text.chunks())
+    }
+
+    pub fn write_history(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+        writer.write_all(HISTORY_MAGIC)?;
+        writer.write_all(&self.content_hash().to_le_bytes())?;
+        self.history.write_to(&mut writer)
+    }
+
+    pub fn read_history(&mut self, mut reader: impl std::io::Read) -> std::io::Result<()> {
+        let mut header = [0; HISTORY_MAGIC.len() + 8];
+        reader.read_exact(&mut header)?;
+        if header[..HISTORY_MAGIC.len()] != *HISTORY_MAGIC
+            || header[HISTORY_MAGIC.len()..] != self.content_hash().to_le_bytes()
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "undo history does not match the text",
+            ));
+        }
+        self.history = History::read_from(&mut reader, self.text.len_chars())?;
+        Ok(())
     }
 
     #[inline]
     pub fn insert_char(&mut self, c: char) -> bool {
+        if self.has_selection() {
+            return self.replace_selection(c.encode_utf8(&mut [0; 4]));
+        }
+        self.record(|editor| editor.at_every_cursor(|editor| editor.insert_char_at_cursor(c)))
+    }
+
+    fn insert_char_at_cursor(&mut self, c: char) -> bool {
         self.create_block_at_cursor();
         if self.window.insert_char(
             &mut self.vlines,
             &mut self.ropes,
             &self.buffers,
+            &mut self.pending,
             c,
             self.pane_height - 1,
         ) {
+            self.sync_text();
             return true;
-        } else if c == ' ' {
-            if self.indent() {
-                // TODO: should the cursor be moved or not?
-                /*
-                for _ in 0..INDENT {
-                    self.window.move_cursor_right_saturating();
-                }
-                */
-                return true;
+        } else if c == ' ' && self.indent() {
+            // TODO: should the cursor be moved or not?
+            /*
+            for _ in 0..INDENT {
+                self.window.move_cursor_right_saturating();
             }
+            */
+            return true;
         }
         false
     }
 
     #[inline]
     pub fn delete_char_forward(&mut self) -> bool {
+        if self.has_selection() {
+            return self.replace_selection("");
+        }
+        self.record(|editor| editor.at_every_cursor(Self::delete_char_forward_at_cursor))
+    }
+
+    fn delete_char_forward_at_cursor(&mut self) -> bool {
         self.create_block_at_cursor();
-        if self
-            .window
-            .delete_char_forward(&mut self.vlines, &mut self.ropes, &self.buffers)
-        {
+        if self.window.delete_char_forward(
+            &mut self.vlines,
+            &mut self.ropes,
+            &self.buffers,
+            &mut self.pending,
+            self.pane_height - 1,
+        ) {
+            self.sync_text();
             return true;
         }
         self.dedent()
@@ -135,11 +600,22 @@ impl Editor {
 
     #[inline]
     pub fn delete_char_backward(&mut self) -> bool {
+        if self.has_selection() {
+            return self.replace_selection("");
+        }
+        self.record(|editor| editor.at_every_cursor(Self::delete_char_backward_at_cursor))
+    }
+
+    fn delete_char_backward_at_cursor(&mut self) -> bool {
         self.create_block_at_cursor();
-        if self
-            .window
-            .delete_char_backward(&mut self.vlines, &mut self.ropes, &self.buffers)
-        {
+        if self.window.delete_char_backward(
+            &mut self.vlines,
+            &mut self.ropes,
+            &self.buffers,
+            &mut self.pending,
+            self.pane_height - 1,
+        ) {
+            self.sync_text();
             return true;
         }
         if self.dedent() {
@@ -171,10 +647,16 @@ impl Editor {
             .move_cursor_left(&self.vlines, &self.ropes, &self.buffers)
     }
 
+    // NOTE: when wrapping is off, the cursor still goes to the next line MAX_WRAP_AT columns past
+    //       the end of a line
     #[inline]
     pub fn move_cursor_right(&mut self) -> bool {
-        self.window
-            .move_cursor_right(&self.vlines, &self.ropes, &self.buffers)
+        self.window.move_cursor_right(
+            &self.vlines,
+            &self.ropes,
+            &self.buffers,
+            self.wrap_at.min(MAX_WRAP_AT),
+        )
     }
 
     #[inline]
@@ -194,16 +676,72 @@ impl Editor {
             .move_cursor_at_end(&self.vlines, &self.ropes, &self.buffers)
     }
 
+    // NOTE: a word is made of letters, digits and underscores, the cursor goes after the next one
+    pub fn move_word_forward(&mut self) -> bool {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let len = self.text.len_chars().saturating_sub(1);
+        let mut idx = self.point_to_char(self.cursor_point());
+        while idx < len && !is_word(self.text.char(idx)) {
+            idx += 1;
+        }
+        while idx < len && is_word(self.text.char(idx)) {
+            idx += 1;
+        }
+        let line = self.text.char_to_line(idx);
+        let point = Point {
+            line,
+            column: idx - self.text.line_to_char(line),
+        };
+        point != self.cursor_point() && self.goto_point(point)
+    }
+
+    pub fn move_word_backward(&mut self) -> bool {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let mut idx = self.point_to_char(self.cursor_point());
+        while idx > 0 && !is_word(self.text.char(idx - 1)) {
+            idx -= 1;
+        }
+        while idx > 0 && is_word(self.text.char(idx - 1)) {
+            idx -= 1;
+        }
+        let line = self.text.char_to_line(idx);
+        let point = Point {
+            line,
+            column: idx - self.text.line_to_char(line),
+        };
+        point != self.cursor_point() && self.goto_point(point)
+    }
+
     #[inline]
     pub fn get_display_lines(&self) -> impl Iterator<Item = DisplayLine<'_>> {
-        self.window
-            .get_display_lines(&self.vlines, &self.ropes, &self.buffers)
+        let matches = self
+            .search
+            .as_ref()
+            .map_or(&[][..], |search| &search.matches[..]);
+        let selection = self.selection();
+        let first_line = self.line_number(self.window.scroll());
+        let mut lines = self.window.get_display_lines(
+            &self.vlines,
+            &self.ropes,
+            &self.buffers,
+            first_line,
+            selection,
+            matches,
+        );
+        lines.tokens = self.tokens();
+        lines.cursors = &self.cursors;
+        lines
             .take(self.pane_height as _)
+            .map(|line| line.scrolled(self.scroll_x))
     }
 
     #[inline]
     pub fn cursor_position<T: From<u16>>(&self) -> (T, T) {
-        self.window.cursor_position()
+        let (_, y) = self.window.cursor_position::<u16>();
+        let x = self
+            .window
+            .cursor_x(&self.vlines, &self.ropes, &self.buffers);
+        (T::from(x.saturating_sub(self.scroll_x as u16)), T::from(y))
     }
 
     #[inline]
@@ -216,6 +754,177 @@ impl Editor {
         self.window.scroll_down(&self.vlines)
     }
 
+    // NOTE: the target line is centered in the pane
+    pub fn goto_line(&mut self, line: usize) -> bool {
+        self.set_cursor_point(Point { line, column: 0 }, self.pane_height / 2)
+            && self.move_cursor_at_start()
+    }
+
+    // NOTE: the row on screen follows the line, as long as it stays in the pane
+    fn goto_point(&mut self, point: Point) -> bool {
+        let cursor = self.cursor_point();
+        let (_, y) = self.window.cursor_position::<u16>();
+        let y = (y as usize + point.line)
+            .saturating_sub(cursor.line)
+            .min(self.pane_height.saturating_sub(1) as usize);
+        self.set_cursor_point(point, y as u16)
+    }
+
+    pub fn cursor_point(&self) -> Point {
+        let (head, column, newlines) =
+            self.window
+                .cursor_offset(&self.vlines, &self.ropes, &self.buffers);
+        Point {
+            line: self.line_number(head) + newlines,
+            column,
+        }
+    }
+
+    pub fn set_cursor_point(&mut self, point: Point, y: u16) -> bool {
+        let Some((target, x, newlines)) = self.point_to_cursor(point) else {
+            return false;
+        };
+        let y = (y as usize).saturating_sub(newlines) as u16;
+        if !self.window.set_cursor(&self.vlines, target, x, y) {
+            self.widen_window(point.line);
+            let Some((target, x, _)) = self.point_to_cursor(point) else {
+                return false;
+            };
+            if !self.window.set_cursor(&self.vlines, target, x, y) {
+                return false;
+            }
+        }
+        for _ in 0..newlines {
+            self.move_cursor_down();
+        }
+        true
+    }
+
+    fn point_to_cursor(&self, point: Point) -> Option<(VLineCursor, u16, usize)> {
+        let mut newlines = 0;
+        let head = self.line_cursor(point.line)?;
+        let buffer = &self.buffers[self.vlines[head].buffer_key];
+        let mut target = head;
+        let mut column = point.column;
+        let head_line = self.line_number(head);
+        if head_line < point.line {
+            newlines = point.line - head_line;
+            while let Some(next) = target.peek_next_visual(&self.vlines) {
+                target = next;
+            }
+        } else if column >= buffer.indent {
+            column -= buffer.indent;
+            loop {
+                let slice = self.vlines[target].slice(&self.ropes);
+                let len_chars =
+                    slice.len_chars() - slice.chars().last().map_or(0, |c| (c == '\n') as usize);
+                if column < len_chars {
+                    break;
+                }
+                let Some(next) = target
+                    .peek_next_visual(&self.vlines)
+                    .filter(|next| next.head_key() == head.head_key())
+                else {
+                    break;
+                };
+                column -= len_chars;
+                target = next;
+            }
+            // NOTE: the chars are drawn on columns after the indentation of the visual line
+            let line = &self.vlines[target];
+            column = buffer.indent
+                + line.continuation.unwrap_or(0)
+                + char_to_column(line.slice(&self.ropes), column);
+        }
+        let x = column.saturating_sub(self.window.indent());
+        Some((target, x as u16, newlines))
+    }
+
+    #[inline]
+    pub fn line_count(&self) -> usize {
+        self.text.len_lines() - 1
+    }
+
+    // NOTE: the buffers are chained in the order of the text and each rope holds whole lines
+    fn first_lines(&self) -> impl Iterator<Item = (BufferKey, usize)> + '_ {
+        self.buffers_from(self.vlines[self.vlines.first()].buffer_key)
+            .map(|key| (key, self.buffers[key].first_line))
+    }
+
+    fn buffers_from(&self, key: BufferKey) -> impl Iterator<Item = BufferKey> + '_ {
+        std::iter::successors(Some(key), |key| {
+            let end = self.buffers[*key].end;
+            (!end.is_null()).then(|| self.vlines[end.head_key()].buffer_key)
+        })
+    }
+
+    // NOTE: the buffers after one whose count of lines changed start at other lines
+    fn shift_first_lines(&mut self, key: BufferKey, lines: isize) {
+        let keys = self.buffers_from(key).skip(1).collect::<Vec<_>>();
+        for key in keys {
+            let buffer = &mut self.buffers[key];
+            buffer.first_line = buffer.first_line.saturating_add_signed(lines);
+        }
+    }
+
+    fn line_number(&self, cursor: VLineCursor) -> usize {
+        let line = &self.vlines[cursor.head_key()];
+        self.buffers[line.buffer_key].first_line
+            + self.ropes[line.buffer_key].byte_to_line(line.start_byte)
+    }
+
+    // NOTE: past the end of the text this is the last line
+    fn line_cursor(&self, line: usize) -> Option<VLineCursor> {
+        let (key, first_line) = self
+            .first_lines()
+            .take_while(|(_, first_line)| *first_line <= line)
+            .last()?;
+        let rope = &self.ropes[key];
+        let line = (line - first_line).min(rope.len_lines().saturating_sub(2));
+        let start_byte = rope.line_to_byte(line);
+        let buffer = &self.buffers[key];
+        buffer
+            .start
+            .iter_logical(&self.vlines)
+            .end_bounded(buffer.end)
+            .find(|cur| self.vlines[cur.head_key()].start_byte == start_byte)
+    }
+
+    fn window_state(&self) -> WindowState {
+        let start = self.window.start();
+        let focus = (start.head_key() != self.vlines.first() || !self.window.end().is_null())
+            .then(|| (self.line_number(start), self.window.indent()));
+        WindowState {
+            focus,
+            cursor: self.cursor_point(),
+            cur_y: self.window.cursor_position::<u16>().1,
+        }
+    }
+
+    fn restore_window_state(&mut self, state: &WindowState) {
+        let focus = state.focus.and_then(|(line, indent)| {
+            self.line_cursor(line)?
+                .iter_logical(&self.vlines)
+                .find_map(|cur| {
+                    let buffer_indent = self.buffers[self.vlines[cur].buffer_key].indent;
+                    cur.detect_indent(&self.vlines, &self.ropes)
+                        .map(|detected| (cur, buffer_indent + detected))
+                })
+                .filter(|(_, detected)| *detected >= indent)
+                .map(|(cur, _)| (cur, indent))
+        });
+        if let Some((start, indent)) = focus {
+            let relative_indent =
+                indent.saturating_sub(self.buffers[self.vlines[start].buffer_key].indent);
+            let key = self.create_block(start, relative_indent);
+            let buffer = &self.buffers[key];
+            self.window = Window::new(buffer.start, buffer.end, buffer.indent);
+        } else {
+            self.whole_window();
+        }
+        self.set_cursor_point(state.cursor, state.cur_y);
+    }
+
     pub fn page_up(&mut self) -> bool {
         let mut changed = false;
         for _ in 0..self.pane_height {
@@ -240,44 +949,88 @@ impl Editor {
         changed
     }
 
+    fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.vlines
+            .iter(self.vlines.first())
+            .flat_map(|(key, line)| {
+                let slice = line.slice(&self.ropes);
+                let indent = if line.is_head()
+                    && !is_blank(
+                        VLineCursor::new(&self.vlines, key).full_slice(&self.vlines, &self.ropes),
+                    ) {
+                    self.buffers[line.buffer_key].indent
+                } else {
+                    0
+                };
+                std::iter::once(&HSPACES[..indent]).chain(slice.chunks())
+            })
+            .filter(|chunk| !chunk.is_empty())
+    }
+
+    pub fn write_to(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+        if self.indentation.tabs {
+            for line in self.file_lines() {
+                writer.write_all(line.as_bytes())?;
+            }
+        } else {
+            for chunk in self.chunks() {
+                writer.write_all(chunk.as_bytes())?;
+            }
+        }
+        writer.flush()
+    }
+
+    // NOTE: a split at the start of a buffer replaces it whole
     fn split_buffer(&mut self, at: VLineCursor, indent: usize) -> BufferKey {
         let line = &self.vlines[at.head_key()];
         let buffer_key = line.buffer_key;
         let buffer = &mut self.buffers[buffer_key];
-        let end = buffer.end;
+        let (end, buffer_wrap_at, buffer_indent) = (buffer.end, buffer.wrap_at, buffer.indent);
+        let mut first_line = buffer.first_line;
         let mut new_rope = if buffer.start == at {
-            self.ropes[buffer_key].clone()
+            self.buffers.remove(buffer_key);
+            self.ropes.remove(buffer_key).unwrap()
         } else {
             buffer.end = at;
             let rope = &mut self.ropes[buffer_key];
             let char_idx = rope.byte_to_char(line.start_byte);
+            first_line += rope.char_to_line(char_idx);
             rope.split_off(char_idx)
         };
-        let mut wrap_at = buffer.wrap_at.saturating_sub(indent);
+        let mut wrap_at = buffer_wrap_at.saturating_sub(indent);
         if wrap_at <= MIN_WRAP_AT {
-            wrap_at = buffer.wrap_at;
+            wrap_at = buffer_wrap_at;
         }
+        let old_rope = new_rope.clone();
         if indent > 0 {
             new_rope = new_rope
                 .lines()
                 .map(|slice| {
-                    if slice.len_chars() > 1 {
+                    if is_blank(slice) {
+                        slice
+                    } else {
                         debug_assert!(
                             slice.len_chars() > indent,
                             "dedent failed: {slice:?} (indent: {indent})",
                         );
                         slice.slice(indent..)
-                    } else {
-                        slice
                     }
                 })
                 .flat_map(|slice| slice.chunks())
                 .collect();
         }
         let new_rope_key = self.ropes.insert(new_rope);
-        let new_buffer = Buffer::new(new_rope_key, at, end, wrap_at, buffer.indent + indent);
+        let new_buffer = Buffer::new(
+            new_rope_key,
+            at,
+            end,
+            first_line,
+            wrap_at,
+            buffer_indent + indent,
+        );
         self.buffers.insert(new_rope_key, new_buffer);
-        at.update_rope(&mut self.vlines, new_rope_key, indent);
+        at.update_rope(&mut self.vlines, new_rope_key, indent, old_rope.slice(..));
+        self.window.clear_position();
         new_rope_key
     }
 
@@ -300,15 +1053,9 @@ impl Editor {
         }
         if let Some(bound) = start_bound {
             let next = bound.peek_next_logical(&self.vlines).unwrap();
-            dbg!(
-                self.vlines[next].slice(&self.ropes),
-                indent,
-                end_bound.is_some()
-            );
             debug_assert_eq!(self.vlines[next].buffer_key, buffer_key);
             self.split_buffer(next, indent)
         } else {
-            dbg!(indent, end_bound.is_some());
             self.split_buffer(buffer_start, indent)
         }
     }
@@ -325,7 +1072,13 @@ impl Editor {
         };
         let mut key = origin;
         loop {
-            self.buffers[key].indent(&mut self.vlines, &self.ropes);
+            self.buffers[key].indent(
+                &mut self.vlines,
+                &self.ropes,
+                &mut self.pending,
+                self.wrap_at,
+            );
+            self.sync_text();
             let Some((next, relative_indent, total_indent)) =
                 self.buffers[key].find_next_block(&self.vlines, &self.ropes, &self.buffers)
             else {
@@ -347,8 +1100,16 @@ impl Editor {
                 break;
             }
             key = self.create_block(next, relative_indent);
-            self.buffers[key].indent(&mut self.vlines, &self.ropes);
+            self.buffers[key].indent(
+                &mut self.vlines,
+                &self.ropes,
+                &mut self.pending,
+                self.wrap_at,
+            );
+            self.sync_text();
         }
+        // NOTE: the lines were rewrapped and the cursor is now at another offset in them
+        self.window.clamp_scroll(&self.vlines);
         true
     }
 
@@ -356,9 +1117,18 @@ impl Editor {
         let Some((origin, indent)) = self.create_block_at_cursor() else {
             return false;
         };
+        if indent < self.indentation.width {
+            return false;
+        }
         let mut key = origin;
         loop {
-            self.buffers[key].dedent(&mut self.vlines, &self.ropes);
+            self.buffers[key].dedent(
+                &mut self.vlines,
+                &self.ropes,
+                &mut self.pending,
+                self.wrap_at,
+            );
+            self.sync_text();
             let Some((next, relative_indent, total_indent)) =
                 self.buffers[key].find_next_block(&self.vlines, &self.ropes, &self.buffers)
             else {
@@ -380,8 +1150,25 @@ impl Editor {
                 break;
             }
             key = self.create_block(next, relative_indent);
-            self.buffers[key].dedent(&mut self.vlines, &self.ropes);
+            self.buffers[key].dedent(
+                &mut self.vlines,
+                &self.ropes,
+                &mut self.pending,
+                self.wrap_at,
+            );
+            self.sync_text();
+        }
+        if let Some(indent) = self
+            .window
+            .start()
+            .iter_logical(&self.vlines)
+            .end_bounded(self.window.end())
+            .map(|cur| self.buffers[self.vlines[cur].buffer_key].indent)
+            .min()
+        {
+            self.window.clamp_indent(indent);
         }
+        self.window.clamp_scroll(&self.vlines);
         true
     }
 
@@ -389,9 +1176,15 @@ impl Editor {
         let Some((key, indent)) = self.create_block_at_cursor() else {
             return false;
         };
-        let Some(indent) = indent.checked_sub(offset * INDENT) else {
+        let Some(indent) = indent.checked_sub(offset * self.indentation.width) else {
             return false;
         };
+        self.window = self.block_window(key, indent);
+        true
+    }
+
+    // NOTE: the blocks around the given one with at least the given indentation
+    fn block_window(&mut self, key: BufferKey, indent: usize) -> Window {
         let first = std::iter::successors(Some(key), |key| {
             let prev = self.buffers[*key].start.peek_prev_logical(&self.vlines)?;
             let buffer = &self.buffers[self.vlines[prev].buffer_key];
@@ -413,105 +1206,154 @@ impl Editor {
         })
         .last()
         .unwrap();
-        self.window = Window::new(
-            &self.buffers,
-            &self.vlines,
-            self.buffers[first].start,
-            self.buffers[last].end,
-        );
-        true
+        Window::new(self.buffers[first].start, self.buffers[last].end, indent)
+    }
+
+    fn window_lines(&self, window: &Window) -> std::ops::Range<usize> {
+        let end = window.end();
+        let end = if end.is_null() {
+            self.line_count()
+        } else {
+            self.line_number(end)
+        };
+        self.line_number(window.start())..end
+    }
+
+    // NOTE: the focus moves out one block at a time until the line is shown, the root window
+    //       shows every line
+    fn widen_window(&mut self, line: usize) -> bool {
+        let key = self.vlines[self.window.start()].buffer_key;
+        let mut indent = self.window.indent();
+        while indent > 0 {
+            indent = indent.saturating_sub(self.indentation.width);
+            if indent == 0 {
+                break;
+            }
+            let window = self.block_window(key, indent);
+            if self.window_lines(&window).contains(&line) {
+                self.window = window;
+                return true;
+            }
+        }
+        self.whole_window()
     }
 
     pub fn set_window_to_cursor(&mut self) -> bool {
-        self.create_window(0)
+        self.focus(|editor| editor.create_window(0))
     }
 
     pub fn set_window_to_parent(&mut self) -> bool {
-        self.create_window(1)
+        self.focus(|editor| editor.create_window(1))
+    }
+
+    // NOTE: the window shows the block the line is part of, with the cursor on the line
+    pub fn set_window_to_line(&mut self, line: usize) -> bool {
+        self.focus(|editor| {
+            editor.goto_line(line) && editor.create_window(0) && editor.goto_line(line)
+        })
     }
 
     pub fn root_window(&mut self) -> bool {
-        let line = &self.vlines[self.vlines.first()];
-        let buffer = &self.buffers[line.buffer_key];
-        self.window = Window::new(
-            &self.buffers,
-            &self.vlines,
-            buffer.start,
-            VLineCursor::null(),
-        );
+        self.focus(Self::whole_window)
+    }
+
+    fn whole_window(&mut self) -> bool {
+        // NOTE: the text may start with an indented block but the root window shows everything
+        let start = VLineCursor::new(&self.vlines, self.vlines.first());
+        self.window = Window::new(start, VLineCursor::null(), 0);
         true
     }
 
+    // NOTE: called before every draw, the wrap follows the pane and the view follows the cursor
     pub fn update_pane_size(&mut self, width: u16, height: u16) {
         self.pane_width = width;
         self.pane_height = height;
+        if self.wrap == WrapMode::Pane {
+            self.rewrap();
+        }
+        self.follow_cursor_x();
+        self.update_highlight();
     }
 
-    pub fn location(&mut self) -> Location<'_> {
-        // TODO should this use self.start instead?
-        let start = self.window.start();
-        let buffer_key = self.create_block(
-            start,
-            start.detect_indent(&self.vlines, &self.ropes).unwrap_or(0),
-        );
-        let mut start = self.buffers[buffer_key].start;
-        if !start.move_prev_logical(&self.vlines) {
-            Location {
-                lines: Default::default(),
+    // NOTE: the blocks around the window, outermost first, each as the line heading it with its
+    //       indentation. The header of a block is the closest line above it with less
+    //       indentation. Nothing is split, every line of a buffer has at least its indentation
+    //       so the ones that cannot be a header are skipped a buffer at a time
+    fn ancestors(&self) -> Vec<(VLineCursor, usize)> {
+        let mut headers = Vec::new();
+        let mut at = self.window.start();
+        let mut below = self.window.indent();
+        while below > 0 {
+            let Some(prev) = at.peek_prev_logical(&self.vlines) else {
+                break;
+            };
+            let buffer = &self.buffers[self.vlines[prev].buffer_key];
+            if buffer.indent >= below {
+                at = buffer.start;
+                continue;
             }
-        } else {
-            let buffer_key = self.create_block(
-                start,
-                start.detect_indent(&self.vlines, &self.ropes).unwrap_or(0),
-            );
-            let buffer = &self.buffers[buffer_key];
-            let end = buffer.end.head_key();
-            let dedent = buffer.indent;
-            if let Some(prev) = start.peek_prev_logical(&self.vlines) {
-                let prev_buffer_key = self.create_block(
-                    prev,
-                    prev.detect_indent(&self.vlines, &self.ropes).unwrap_or(0),
-                );
-                if prev_buffer_key != buffer_key {
-                    if start.full_slice(&self.vlines, &self.ropes).chars().next() != Some('}') {
-                        while let Some(prev) = start.peek_prev_logical(&self.vlines) {
-                            let prev_buffer_key = self.create_block(
-                                prev,
-                                prev.detect_indent(&self.vlines, &self.ropes).unwrap_or(0),
-                            );
-                            if prev.full_slice(&self.vlines, &self.ropes).chars().next()
-                                == Some('}')
-                            {
-                                break;
-                            }
-                            let prev_buffer = &self.buffers[prev_buffer_key];
-                            if prev_buffer.indent < dedent {
-                                break;
-                            }
-                            start = prev;
-                            if prev_buffer.indent == dedent {
-                                break;
-                            }
-                        }
-                    }
-                }
+            at = prev;
+            let Some(detected) = prev.detect_indent(&self.vlines, &self.ropes) else {
+                continue;
+            };
+            let indent = buffer.indent + detected;
+            if indent < below {
+                headers.push((prev, indent));
+                below = indent;
             }
-            Location {
-                lines: DisplayLineIter {
-                    ropes: &self.ropes,
-                    buffers: &self.buffers,
-                    vlines_iter: self.vlines.iter(start.key(&self.vlines)),
-                    end,
-                    dedent,
-                    prepend_newlines: 0,
-                    empty_slice: self.vlines[start].slice(&self.ropes).slice(0..0),
-                }
-                .collect(),
+        }
+        headers.reverse();
+        headers
+    }
+
+    pub fn ancestor_count(&self) -> usize {
+        self.ancestors().len()
+    }
+
+    // NOTE: one level up is the same as set_window_to_parent, the cursor goes to the header
+    pub fn set_window_to_ancestor(&mut self, levels: usize) -> bool {
+        let headers = self.ancestors();
+        match headers.len().checked_sub(levels) {
+            Some(i) if i < headers.len() => {
+                let line = self.line_number(headers[i].0);
+                self.set_window_to_line(line)
             }
+            _ => false,
+        }
+    }
+
+    // NOTE: a breadcrumb of the headers of every block around the window, one line each
+    pub fn location(&self) -> Location<'_> {
+        let headers = self.ancestors();
+        let dedent = headers.first().map_or(0, |(_, indent)| *indent);
+        Location {
+            lines: headers
+                .into_iter()
+                .map(|(cursor, indent)| {
+                    let slice = cursor.full_slice(&self.vlines, &self.ropes);
+                    let start = slice.chars().take_while(|c| *c == ' ').count();
+                    let end = slice.len_chars() - (slice.chars().last() == Some('\n')) as usize;
+                    DisplayLine {
+                        slice: slice.slice(start.min(end)..end),
+                        indent: &HSPACES[..indent - dedent],
+                        line_number: Some(self.line_number(cursor)),
+                        selected: None,
+                        highlights: Vec::new(),
+                        tokens: Vec::new(),
+                        cursors: Vec::new(),
+                    }
+                })
+                .collect(),
         }
     }
 }
 
+impl std::fmt::Display for Editor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.chunks().try_for_each(|chunk| f.write_str(chunk))
+    }
+}
+
 #[derive(derive_more::Debug)]
 pub struct DisplayLineIter<'v, 'r, 'b> {
     #[debug(skip)]
@@ -525,6 +1367,15 @@ pub struct DisplayLineIter<'v, 'r, 'b> {
     pub prepend_newlines: usize,
     #[debug(skip)]
     pub empty_slice: RopeSlice<'r>,
+    pub selection: Option<(Point, Point)>,
+    #[debug(skip)]
+    pub matches: &'b [(Point, Point)],
+    #[debug(skip)]
+    pub tokens: &'b [(Point, Point, TokenKind)],
+    pub cursors: &'b [Point],
+    pub line: usize,
+    pub column: usize,
+    pub started: bool,
 }
 
 impl<'v, 'r, 'b> Iterator for DisplayLineIter<'v, 'r, 'b> {
@@ -534,19 +1385,32 @@ impl<'v, 'r, 'b> Iterator for DisplayLineIter<'v, 'r, 'b> {
         if self.prepend_newlines > 0 {
             self.prepend_newlines -= 1;
             return Some(DisplayLine {
-                slice: self.empty_slice.clone(),
-                indent: &"",
-                continuation: false,
+                slice: self.empty_slice,
+                indent: "",
+                line_number: None,
+                selected: None,
+                highlights: Vec::new(),
+                tokens: Vec::new(),
+                cursors: Vec::new(),
             });
         }
         let (key, line) = self.vlines_iter.next()?;
         if key == self.end {
             return None;
         }
+        let buffer = &self.buffers[line.buffer_key];
+        if line.is_head() {
+            if self.started {
+                self.line += 1;
+            }
+            let rest = self.ropes[line.buffer_key].byte_slice(line.start_byte..);
+            self.column = if is_blank(rest) { 0 } else { buffer.indent };
+        }
+        self.started = true;
         debug_assert!(self.buffers[line.buffer_key].indent >= self.dedent);
         let indent =
             self.buffers[line.buffer_key].indent - self.dedent + line.continuation.unwrap_or(0);
-        let slice = line.slice(&self.ropes);
+        let slice = line.slice(self.ropes);
         debug_assert!(
             slice
                 .chars_at(slice.len_chars())
@@ -556,10 +1420,60 @@ impl<'v, 'r, 'b> Iterator for DisplayLineIter<'v, 'r, 'b> {
             "newline in DisplayLine: {:?}",
             slice
         );
+        let len_chars = slice.len_chars();
+        let range_on_line = |(start, end): (Point, Point)| {
+            if self.line < start.line || self.line > end.line {
+                return None;
+            }
+            let from = if self.line == start.line {
+                start.column.saturating_sub(self.column).min(len_chars)
+            } else {
+                0
+            };
+            let to = if self.line == end.line {
+                end.column.saturating_sub(self.column).min(len_chars)
+            } else {
+                len_chars
+            };
+            (from < to).then_some(from..to)
+        };
+        let selected = self.selection.and_then(range_on_line);
+        let first_match = self
+            .matches
+            .partition_point(|(_, end)| end.line < self.line);
+        let highlights = self.matches[first_match..]
+            .iter()
+            .take_while(|(start, _)| start.line <= self.line)
+            .filter_map(|range| range_on_line(*range))
+            .collect();
+        let first_token = self
+            .tokens
+            .partition_point(|(_, end, _)| end.line < self.line);
+        let tokens = self.tokens[first_token..]
+            .iter()
+            .take_while(|(start, _, _)| start.line <= self.line)
+            .filter_map(|(start, end, kind)| Some((range_on_line((*start, *end))?, *kind)))
+            .collect();
+        let cursors = self
+            .cursors
+            .iter()
+            .filter_map(|point| {
+                let next = Point {
+                    column: point.column + 1,
+                    ..*point
+                };
+                range_on_line((*point, next))
+            })
+            .collect();
+        self.column += len_chars;
         Some(DisplayLine {
             slice,
             indent: &HSPACES[..indent],
-            continuation: line.is_continuation(),
+            line_number: line.is_head().then_some(self.line),
+            selected,
+            highlights,
+            tokens,
+            cursors,
         })
     }
 }
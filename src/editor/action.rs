@@ -0,0 +1,158 @@
+use super::*;
+
+macro_rules! actions {
+    ($($action:ident),* $(,)?) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Action {
+            $($action),*
+        }
+
+        impl Action {
+            pub const ALL: &[Action] = &[$(Action::$action),*];
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Action::$action => stringify!($action)),*
+                }
+            }
+        }
+    };
+}
+
+// NOTE: the actions after Cancel need more than the editor and are handled by the application
+actions! {
+    InsertNewline,
+    DeleteBackward,
+    DeleteForward,
+    MoveCursorUp,
+    MoveCursorDown,
+    MoveCursorLeft,
+    MoveCursorRight,
+    MoveCursorAt0,
+    MoveCursorAtStart,
+    MoveCursorAtEnd,
+    MoveWordForward,
+    MoveWordBackward,
+    SelectUp,
+    SelectDown,
+    SelectLeft,
+    SelectRight,
+    SelectToStart,
+    SelectToEnd,
+    SetMark,
+    AddCursorAbove,
+    AddCursorBelow,
+    AddCursorAtNextMatch,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    IndentSelection,
+    DedentSelection,
+    SearchNext,
+    SearchPrev,
+    Undo,
+    Redo,
+    UndoEarlier,
+    UndoLater,
+    UndoNextBranch,
+    SetWindowToParent,
+    SetWindowToCursor,
+    RootWindow,
+    WindowBack,
+    WindowForward,
+    Cancel,
+    Quit,
+    Save,
+    NextPane,
+    IndentSelectionOrNextPane,
+    Copy,
+    Cut,
+    KillLine,
+    Paste,
+    PasteNext,
+    KillRing,
+    PickAncestor,
+    CycleWrap,
+    WidenWrap,
+    NarrowWrap,
+    ToggleWordWrap,
+    NextIndentWidth,
+    ToggleTabs,
+    ToggleRelativeNumbers,
+    ToggleVim,
+    UndoTravel,
+    GotoLine,
+    Search,
+    Replace,
+    ToggleDebug,
+    DebugScrollUp,
+    DebugScrollDown,
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|action| action.name() == s)
+            .ok_or_else(|| format!("unknown action: {s}"))
+    }
+}
+
+impl Editor {
+    pub fn apply(&mut self, action: Action) -> bool {
+        match action {
+            Action::InsertNewline => self.insert_char('\n'),
+            Action::DeleteBackward => self.delete_char_backward(),
+            Action::DeleteForward => self.delete_char_forward(),
+            Action::MoveCursorUp => self.deselect(Self::move_cursor_up),
+            Action::MoveCursorDown => self.deselect(Self::move_cursor_down),
+            Action::MoveCursorLeft => self.deselect(Self::move_cursor_left),
+            Action::MoveCursorRight => self.deselect(Self::move_cursor_right),
+            Action::MoveCursorAt0 => self.move_cursor_at_0(),
+            Action::MoveCursorAtStart => self.deselect(Self::move_cursor_at_start),
+            Action::MoveCursorAtEnd => self.deselect(Self::move_cursor_at_end),
+            Action::MoveWordForward => self.deselect(Self::move_word_forward),
+            Action::MoveWordBackward => self.deselect(Self::move_word_backward),
+            Action::SelectUp => self.select(Self::move_cursor_up),
+            Action::SelectDown => self.select(Self::move_cursor_down),
+            Action::SelectLeft => self.select(Self::move_cursor_left),
+            Action::SelectRight => self.select(Self::move_cursor_right),
+            Action::SelectToStart => self.select(Self::move_cursor_at_start),
+            Action::SelectToEnd => self.select(Self::move_cursor_at_end),
+            Action::SetMark => self.set_mark(),
+            Action::AddCursorAbove => self.add_cursor_above(),
+            Action::AddCursorBelow => self.add_cursor_below(),
+            Action::AddCursorAtNextMatch => self.add_cursor_at_next_match(),
+            Action::ScrollUp => self.scroll_up(),
+            Action::ScrollDown => self.scroll_down(),
+            Action::PageUp => self.page_up(),
+            Action::PageDown => self.page_down(),
+            Action::IndentSelection => self.indent_selection(),
+            Action::DedentSelection => self.dedent_selection(),
+            Action::SearchNext => self.search_next(),
+            Action::SearchPrev => self.search_prev(),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::UndoEarlier => self.undo_earlier(),
+            Action::UndoLater => self.undo_later(),
+            Action::UndoNextBranch => self.undo_next_branch(),
+            Action::SetWindowToParent => self.set_window_to_parent(),
+            Action::SetWindowToCursor => self.set_window_to_cursor(),
+            Action::RootWindow => self.root_window(),
+            Action::WindowBack => self.window_back(),
+            Action::WindowForward => self.window_forward(),
+            Action::Cancel => self.clear_selection() | self.clear_search() | self.clear_cursors(),
+            _ => false,
+        }
+    }
+}
@@ -1,5 +1,14 @@
 use super::*;
 
+// NOTE: an edit of a buffer as it is in the text, with the indentation of the buffer, the char
+//       index is counted from the start of the line of the rope
+#[derive(Debug)]
+pub struct BufferEdit {
+    pub key: BufferKey,
+    pub line: usize,
+    pub edit: Edit,
+}
+
 #[derive(derive_more::Debug)]
 pub struct Buffer {
     #[debug(skip)]
@@ -8,6 +17,9 @@ pub struct Buffer {
     pub start: VLineCursor,
     #[debug(skip)]
     pub end: VLineCursor,
+    // NOTE: the line of the text the buffer starts at, kept as the blocks are split, joined and
+    //       edited
+    pub first_line: usize,
     pub wrap_at: usize,
     pub indent: usize,
 }
@@ -17,29 +29,31 @@ impl Buffer {
         key: BufferKey,
         start: VLineCursor,
         end: VLineCursor,
+        first_line: usize,
         wrap_at: usize,
         indent: usize,
     ) -> Self {
-        debug_assert!(wrap_at < HSPACES.len());
+        debug_assert!(wrap_at < HSPACES.len() || wrap_at > NO_WRAP / 2);
         Self {
             key,
             start,
             end,
+            first_line,
             wrap_at,
             indent,
         }
     }
 
-    #[inline]
     pub fn insert(
         &self,
         vlines: &mut VLines,
         ropes: &mut RopeMap,
+        edits: &mut Vec<BufferEdit>,
         char_idx: usize,
         text: &str,
         cursor: VLineCursor,
     ) -> VLineKey {
-        ropes[self.key].insert(char_idx, text);
+        self.edit_rope(ropes, edits, char_idx..char_idx, text);
         cursor.insert(vlines, ropes, text.len(), self.wrap_at)
     }
 
@@ -48,30 +62,73 @@ impl Buffer {
         &self,
         vlines: &mut VLines,
         ropes: &mut RopeMap,
+        edits: &mut Vec<BufferEdit>,
         char_idx: usize,
         c: char,
         cursor: VLineCursor,
     ) {
-        let rope = &mut ropes[self.key];
-        let len_chars_before = rope.len_chars();
-        rope.insert_char(char_idx, c);
-        let bytes = rope.len_chars() - len_chars_before;
-        cursor.insert(vlines, ropes, bytes, self.wrap_at);
+        self.insert(
+            vlines,
+            ropes,
+            edits,
+            char_idx,
+            c.encode_utf8(&mut [0; 4]),
+            cursor,
+        );
     }
 
-    #[inline]
     pub fn remove(
         &self,
         vlines: &mut VLines,
         ropes: &mut RopeMap,
-        char_idx: usize,
+        edits: &mut Vec<BufferEdit>,
+        range: std::ops::Range<usize>,
         cursor: VLineCursor,
+    ) {
+        let rope = &ropes[self.key];
+        let start_byte = rope.char_to_byte(range.start);
+        let bytes = rope.char_to_byte(range.end) - start_byte;
+        self.edit_rope(ropes, edits, range, "");
+        cursor.remove(vlines, ropes, start_byte, bytes, self.wrap_at);
+    }
+
+    // NOTE: the lines are left as they were, they have to be laid out again
+    pub fn edit_rope(
+        &self,
+        ropes: &mut RopeMap,
+        edits: &mut Vec<BufferEdit>,
+        range: std::ops::Range<usize>,
+        text: &str,
     ) {
         let rope = &mut ropes[self.key];
-        let len_chars_before = rope.len_chars();
-        rope.remove(char_idx..=char_idx);
-        let bytes = len_chars_before - rope.len_chars();
-        cursor.remove(vlines, ropes, bytes, self.wrap_at);
+        let line = rope.char_to_line(range.start);
+        let old = self.text_of_lines(rope, line..rope.char_to_line(range.end) + 1);
+        rope.remove(range.clone());
+        rope.insert(range.start, text);
+        let new = self.text_of_lines(rope, line..line + 1 + text.matches('\n').count());
+        self.report(edits, line, &old, &new);
+    }
+
+    // NOTE: blank lines are written without the indentation, like `Editor::chunks` does
+    fn text_of_lines(&self, rope: &Rope, lines: std::ops::Range<usize>) -> String {
+        let mut text = String::new();
+        for line in lines.map(|i| rope.line(i)) {
+            if !is_blank(line) {
+                text.push_str(&HSPACES[..self.indent]);
+            }
+            text.extend(line.chunks());
+        }
+        text
+    }
+
+    fn report(&self, edits: &mut Vec<BufferEdit>, line: usize, old: &str, new: &str) {
+        if let Some(edit) = Edit::between(old, new) {
+            edits.push(BufferEdit {
+                key: self.key,
+                line,
+                edit,
+            });
+        }
     }
 
     pub fn rewrap(&self, vlines: &mut VLines, ropes: &RopeMap) {
@@ -84,16 +141,59 @@ impl Buffer {
         }
     }
 
-    pub fn indent(&mut self, vlines: &mut VLines, ropes: &RopeMap) {
-        self.indent += INDENT;
-        self.wrap_at = (WRAP_AT - self.indent).max(MIN_WRAP_AT);
+    pub fn set_width(&mut self, vlines: &mut VLines, ropes: &RopeMap, width: usize) {
+        self.wrap_at = width.saturating_sub(self.indent).max(MIN_WRAP_AT);
         self.rewrap(vlines, ropes);
     }
 
-    pub fn dedent(&mut self, vlines: &mut VLines, ropes: &RopeMap) {
-        self.indent -= INDENT;
-        self.wrap_at = WRAP_AT - self.indent;
-        self.rewrap(vlines, ropes);
+    pub fn indent(
+        &mut self,
+        vlines: &mut VLines,
+        ropes: &RopeMap,
+        edits: &mut Vec<BufferEdit>,
+        width: usize,
+    ) {
+        self.set_indent(
+            vlines,
+            ropes,
+            edits,
+            width,
+            self.indent + vlines.indent_width(),
+        );
+    }
+
+    pub fn dedent(
+        &mut self,
+        vlines: &mut VLines,
+        ropes: &RopeMap,
+        edits: &mut Vec<BufferEdit>,
+        width: usize,
+    ) {
+        self.set_indent(
+            vlines,
+            ropes,
+            edits,
+            width,
+            self.indent - vlines.indent_width(),
+        );
+    }
+
+    // NOTE: the rope is left as is, every line of the text that is not blank moves
+    fn set_indent(
+        &mut self,
+        vlines: &mut VLines,
+        ropes: &RopeMap,
+        edits: &mut Vec<BufferEdit>,
+        width: usize,
+        indent: usize,
+    ) {
+        let rope = &ropes[self.key];
+        let lines = 0..rope.len_lines() - 1;
+        let old = self.text_of_lines(rope, lines.clone());
+        self.indent = indent;
+        let new = self.text_of_lines(rope, lines);
+        self.report(edits, 0, &old, &new);
+        self.set_width(vlines, ropes, width);
     }
 
     pub fn find_next_block(
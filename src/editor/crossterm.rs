@@ -1,104 +1,46 @@
 use super::*;
-use ::crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ::crossterm::event::{self, Event, KeyEvent, KeyModifiers};
+
+// NOTE: the keys no binding can name are given back
+impl TryFrom<KeyEvent> for Keystroke {
+    type Error = KeyEvent;
+
+    fn try_from(key: KeyEvent) -> std::result::Result<Self, Self::Error> {
+        let code = match key.code {
+            event::KeyCode::Char(c) => KeyCode::Char(c),
+            event::KeyCode::Enter => KeyCode::Enter,
+            event::KeyCode::Backspace => KeyCode::Backspace,
+            event::KeyCode::Delete => KeyCode::Delete,
+            event::KeyCode::Tab => KeyCode::Tab,
+            event::KeyCode::BackTab => KeyCode::BackTab,
+            event::KeyCode::Esc => KeyCode::Esc,
+            event::KeyCode::Up => KeyCode::Up,
+            event::KeyCode::Down => KeyCode::Down,
+            event::KeyCode::Left => KeyCode::Left,
+            event::KeyCode::Right => KeyCode::Right,
+            event::KeyCode::Home => KeyCode::Home,
+            event::KeyCode::End => KeyCode::End,
+            event::KeyCode::PageUp => KeyCode::PageUp,
+            event::KeyCode::PageDown => KeyCode::PageDown,
+            event::KeyCode::F(n) => KeyCode::F(n),
+            _ => return Err(key),
+        };
+        Ok(Keystroke::new(
+            code,
+            key.modifiers.contains(KeyModifiers::CONTROL),
+            key.modifiers.contains(KeyModifiers::ALT),
+            key.modifiers.contains(KeyModifiers::SHIFT),
+        ))
+    }
+}
 
 impl Editor {
+    // NOTE: the keys go through the keymap, only the events without a key or with a key no
+    //       binding can name end up here
     pub fn handle_event(&mut self, event: Event) -> bool {
         match event {
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('0'),
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.move_cursor_at_0(),
-            Event::Key(KeyEvent {
-                code: KeyCode::Char(c),
-                modifiers,
-                ..
-            }) if modifiers == KeyModifiers::SHIFT || modifiers == KeyModifiers::NONE => {
-                self.insert_char(c)
-            }
-            Event::Key(KeyEvent {
-                code: KeyCode::Enter,
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.insert_char('\n'),
-            Event::Key(KeyEvent {
-                code: KeyCode::Backspace,
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.delete_char_backward(),
-            Event::Key(KeyEvent {
-                code: KeyCode::Delete,
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.delete_char_forward(),
-            Event::Key(KeyEvent {
-                code: KeyCode::Up,
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.move_cursor_up(),
-            Event::Key(KeyEvent {
-                code: KeyCode::Down,
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.move_cursor_down(),
-            Event::Key(KeyEvent {
-                code: KeyCode::Left,
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.move_cursor_left(),
-            Event::Key(KeyEvent {
-                code: KeyCode::Right,
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.move_cursor_right(),
-            Event::Key(KeyEvent {
-                code: KeyCode::Up,
-                modifiers: KeyModifiers::SHIFT,
-                ..
-            }) => self.scroll_up(),
-            Event::Key(KeyEvent {
-                code: KeyCode::Down,
-                modifiers: KeyModifiers::SHIFT,
-                ..
-            }) => self.scroll_down(),
-            Event::Key(KeyEvent {
-                code: KeyCode::PageUp,
-                ..
-            }) => self.page_up(),
-            Event::Key(KeyEvent {
-                code: KeyCode::PageDown,
-                ..
-            }) => self.page_down(),
-            Event::Key(KeyEvent {
-                code: KeyCode::Home,
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.move_cursor_at_start(),
-            Event::Key(KeyEvent {
-                code: KeyCode::End,
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.move_cursor_at_end(),
-            Event::Key(KeyEvent {
-                code: KeyCode::F(7),
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.set_window_to_parent(),
-            Event::Key(KeyEvent {
-                code: KeyCode::F(8),
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.set_window_to_cursor(),
-            Event::Key(KeyEvent {
-                code: KeyCode::F(6),
-                modifiers: KeyModifiers::NONE,
-                ..
-            }) => self.root_window(),
-            Event::Mouse(_) => false,
-            _ => {
-                dbg!(event);
-                false
-            }
+            Event::Paste(text) => self.insert_str(&text),
+            _ => false,
         }
     }
 }
@@ -0,0 +1,139 @@
+use super::*;
+
+// NOTE: the extra cursors are logical points like the selection, the window only has the main
+//       one. An edit is made at each cursor in turn through the window, thus the buffer of its
+//       block, from the last cursor in the text to the first: the cursors not edited yet are
+//       before every change made so far and keep their points, the ones already edited are kept
+//       as chars of the text, moved by each edit the buffers report before them
+
+impl Editor {
+    #[inline]
+    pub fn has_cursors(&self) -> bool {
+        !self.cursors.is_empty()
+    }
+
+    pub fn clear_cursors(&mut self) -> bool {
+        let cleared = self.has_cursors();
+        self.cursors.clear();
+        cleared
+    }
+
+    pub fn add_cursor_above(&mut self) -> bool {
+        let cursor = self.cursor_point();
+        cursor.line > 0
+            && self.add_cursor(Point {
+                line: cursor.line - 1,
+                ..cursor
+            })
+    }
+
+    pub fn add_cursor_below(&mut self) -> bool {
+        let cursor = self.cursor_point();
+        cursor.line + 1 < self.line_count()
+            && self.add_cursor(Point {
+                line: cursor.line + 1,
+                ..cursor
+            })
+    }
+
+    // NOTE: the next whole word equal to the one under the cursor gets the main cursor, at the
+    //       same place in the word, the search goes around the end of the text
+    pub fn add_cursor_at_next_match(&mut self) -> bool {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let idx = self.point_to_char(self.cursor_point());
+        let len = self.text.len_chars();
+        let mut start = idx;
+        while start > 0 && is_word(self.text.char(start - 1)) {
+            start -= 1;
+        }
+        let mut end = idx;
+        while end < len && is_word(self.text.char(end)) {
+            end += 1;
+        }
+        if start == end {
+            return false;
+        }
+        let word = self.text.slice(start..end).to_string();
+        let text = self.text.to_string();
+        let byte_start = self.text.char_to_byte(start);
+        let byte_end = self.text.char_to_byte(end);
+        let whole = |at: usize| {
+            !text[..at].chars().next_back().is_some_and(is_word)
+                && !text[at + word.len()..].chars().next().is_some_and(is_word)
+        };
+        let offset = idx - start;
+        let found = text[byte_end..]
+            .match_indices(&word)
+            .map(|(at, _)| byte_end + at)
+            .chain(text[..byte_start].match_indices(&word).map(|(at, _)| at))
+            .filter(|at| whole(*at))
+            .map(|at| self.text.byte_to_char(at) + offset)
+            .map(|idx| {
+                let line = self.text.char_to_line(idx);
+                Point {
+                    line,
+                    column: idx - self.text.line_to_char(line),
+                }
+            })
+            .find(|point| !self.cursors.contains(point));
+        found.is_some_and(|point| self.add_cursor(point))
+    }
+
+    // NOTE: the main cursor goes to the new place, an extra one stays where it was
+    fn add_cursor(&mut self, point: Point) -> bool {
+        let cursor = self.cursor_point();
+        let point = self.clamp_point(point);
+        if point == cursor || !self.goto_point(point) {
+            return false;
+        }
+        self.cursors.retain(|extra| *extra != point);
+        if !self.cursors.contains(&cursor) {
+            self.cursors.push(cursor);
+        }
+        true
+    }
+
+    pub(super) fn at_every_cursor(&mut self, edit: impl Fn(&mut Self) -> bool) -> bool {
+        if self.cursors.is_empty() {
+            return edit(self);
+        }
+        let main = self.cursor_point();
+        let mut points = self.cursors.clone();
+        points.push(main);
+        points.sort_unstable();
+        points.dedup();
+        let mut edited = false;
+        let mut chars: Vec<(usize, bool)> = Vec::with_capacity(points.len());
+        for point in points.into_iter().rev() {
+            let seen = self.edits.len();
+            if self.goto_point(point) {
+                edited |= edit(self);
+            }
+            for edit in &self.edits[seen..] {
+                let end = edit.char_idx + edit.removed.chars().count();
+                let inserted = edit.inserted.chars().count();
+                for (idx, _) in &mut chars {
+                    if *idx >= end {
+                        *idx = *idx + inserted - (end - edit.char_idx);
+                    } else if *idx > edit.char_idx {
+                        *idx = edit.char_idx + inserted;
+                    }
+                }
+            }
+            chars.push((self.point_to_char(self.cursor_point()), point == main));
+        }
+        let mut main = main;
+        self.cursors.clear();
+        for (idx, is_main) in chars {
+            let point = self.char_to_point(idx);
+            if is_main {
+                main = point;
+            } else if !self.cursors.contains(&point) {
+                self.cursors.push(point);
+            }
+        }
+        self.cursors.retain(|point| *point != main);
+        self.goto_point(main);
+        edited
+    }
+}
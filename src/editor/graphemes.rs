@@ -0,0 +1,199 @@
+use super::*;
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete};
+use unicode_width::UnicodeWidthStr;
+
+// NOTE: columns are counted like ratatui draws them, graphemes with a control character are not
+//       drawn at all and take no column
+pub fn grapheme_width(grapheme: RopeSlice) -> usize {
+    if grapheme.chars().any(char::is_control) {
+        return 0;
+    }
+    match grapheme.as_str() {
+        Some(s) => s.width(),
+        None => grapheme.to_string().width(),
+    }
+}
+
+#[derive(derive_more::Debug, Clone)]
+pub struct Graphemes<'r> {
+    #[debug(skip)]
+    slice: RopeSlice<'r>,
+    #[debug(skip)]
+    chunks: iter::Chunks<'r>,
+    #[debug(skip)]
+    chunk: &'r str,
+    chunk_start: usize,
+    #[debug(skip)]
+    cursor: GraphemeCursor,
+}
+
+// NOTE: the graphemes of a line stop at its newline
+pub fn graphemes(slice: RopeSlice<'_>) -> Graphemes<'_> {
+    let mut chunks = slice.chunks();
+    let chunk = chunks.next().unwrap_or("");
+    Graphemes {
+        slice,
+        chunks,
+        chunk,
+        chunk_start: 0,
+        cursor: GraphemeCursor::new(0, slice.len_bytes(), true),
+    }
+}
+
+impl<'r> Iterator for Graphemes<'r> {
+    type Item = RopeSlice<'r>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.cursor.cur_cursor();
+        let end = loop {
+            match self.cursor.next_boundary(self.chunk, self.chunk_start) {
+                Ok(None) => return None,
+                Ok(Some(end)) => break end,
+                Err(GraphemeIncomplete::NextChunk) => {
+                    self.chunk_start += self.chunk.len();
+                    self.chunk = self.chunks.next().unwrap_or("");
+                }
+                Err(GraphemeIncomplete::PreContext(idx)) => {
+                    let (chunk, chunk_start, _, _) = self.slice.chunk_at_byte(idx - 1);
+                    self.cursor.provide_context(chunk, chunk_start);
+                }
+                Err(err) => unreachable!("{err:?}"),
+            }
+        };
+        let grapheme = self.slice.byte_slice(start..end);
+        (!grapheme.chars().any(|c| c == '\n')).then_some(grapheme)
+    }
+}
+
+pub fn text_width(slice: RopeSlice) -> usize {
+    graphemes(slice).map(grapheme_width).sum()
+}
+
+// NOTE: a column inside a wide grapheme is at its start, the columns past the end of the text are
+//       returned as trailing spaces
+pub fn column_to_char(slice: RopeSlice, column: usize) -> (usize, usize, usize) {
+    let mut char_idx = 0;
+    let mut width = 0;
+    for grapheme in graphemes(slice) {
+        let grapheme_width = grapheme_width(grapheme);
+        if width + grapheme_width > column {
+            return (char_idx, width, 0);
+        }
+        char_idx += grapheme.len_chars();
+        width += grapheme_width;
+    }
+    (char_idx, width, column - width)
+}
+
+pub fn char_to_column(slice: RopeSlice, char_idx: usize) -> usize {
+    let mut chars = 0;
+    let mut width = 0;
+    for grapheme in graphemes(slice) {
+        chars += grapheme.len_chars();
+        if chars > char_idx {
+            return width;
+        }
+        width += grapheme_width(grapheme);
+    }
+    width + char_idx.saturating_sub(chars)
+}
+
+pub fn next_column(slice: RopeSlice, column: usize) -> Option<usize> {
+    let mut width = 0;
+    graphemes(slice).find_map(|grapheme| {
+        width += grapheme_width(grapheme);
+        (width > column).then_some(width)
+    })
+}
+
+// NOTE: returns where the line has to be split for the graphemes to fit in the width, at least
+//       `min_chars` are kept on the line
+pub fn split_at_width(slice: RopeSlice, width: usize, min_chars: usize) -> Option<usize> {
+    let mut char_idx = 0;
+    let mut columns = 0;
+    for grapheme in graphemes(slice) {
+        columns += grapheme_width(grapheme);
+        if columns > width && char_idx >= min_chars.max(1) {
+            return Some(char_idx);
+        }
+        char_idx += grapheme.len_chars();
+    }
+    None
+}
+
+// NOTE: the line is broken at the last word boundary that fits, a word longer than the width is
+//       still broken where it has to be
+pub fn split_at_word(slice: RopeSlice, width: usize, min_chars: usize) -> Option<usize> {
+    let split_idx = split_at_width(slice, width, min_chars)?;
+    let mut char_idx = 0;
+    let mut word_idx = None;
+    let mut prev: Option<RopeSlice> = None;
+    for grapheme in graphemes(slice) {
+        if char_idx > split_idx {
+            break;
+        }
+        if char_idx >= min_chars.max(1) && prev.is_some_and(|prev| is_word_boundary(prev, grapheme))
+        {
+            word_idx = Some(char_idx);
+        }
+        prev = Some(grapheme);
+        char_idx += grapheme.len_chars();
+    }
+    Some(word_idx.unwrap_or(split_idx))
+}
+
+// NOTE: after whitespace, after punctuation that does not belong to an identifier or a string,
+//       and around wide graphemes which are usually written without spaces
+fn is_word_boundary(prev: RopeSlice, next: RopeSlice) -> bool {
+    let (Some(a), Some(b)) = (prev.chars().next(), next.chars().next()) else {
+        return false;
+    };
+    if a.is_whitespace() || b.is_whitespace() {
+        return a.is_whitespace() && !b.is_whitespace();
+    }
+    if grapheme_width(prev) > 1 || grapheme_width(next) > 1 {
+        return true;
+    }
+    a.is_ascii_punctuation() && !matches!(a, '_' | '"' | '\'' | '`') && b.is_alphanumeric()
+}
+
+pub fn next_grapheme_boundary(slice: RopeSlice, char_idx: usize) -> usize {
+    let byte_idx = slice.char_to_byte(char_idx);
+    let (mut chunk, mut chunk_start, _, _) = slice.chunk_at_byte(byte_idx);
+    let mut cursor = GraphemeCursor::new(byte_idx, slice.len_bytes(), true);
+    loop {
+        match cursor.next_boundary(chunk, chunk_start) {
+            Ok(None) => return slice.len_chars(),
+            Ok(Some(idx)) => return slice.byte_to_char(idx),
+            Err(GraphemeIncomplete::NextChunk) => {
+                chunk_start += chunk.len();
+                chunk = slice.chunk_at_byte(chunk_start).0;
+            }
+            Err(GraphemeIncomplete::PreContext(idx)) => {
+                let (chunk, chunk_start, _, _) = slice.chunk_at_byte(idx - 1);
+                cursor.provide_context(chunk, chunk_start);
+            }
+            Err(err) => unreachable!("{err:?}"),
+        }
+    }
+}
+
+pub fn prev_grapheme_boundary(slice: RopeSlice, char_idx: usize) -> usize {
+    let byte_idx = slice.char_to_byte(char_idx);
+    let (mut chunk, mut chunk_start, _, _) = slice.chunk_at_byte(byte_idx);
+    let mut cursor = GraphemeCursor::new(byte_idx, slice.len_bytes(), true);
+    loop {
+        match cursor.prev_boundary(chunk, chunk_start) {
+            Ok(None) => return 0,
+            Ok(Some(idx)) => return slice.byte_to_char(idx),
+            Err(GraphemeIncomplete::PrevChunk) => {
+                (chunk, chunk_start, _, _) = slice.chunk_at_byte(chunk_start - 1);
+            }
+            Err(GraphemeIncomplete::PreContext(idx)) => {
+                let (chunk, chunk_start, _, _) = slice.chunk_at_byte(idx - 1);
+                cursor.provide_context(chunk, chunk_start);
+            }
+            Err(err) => unreachable!("{err:?}"),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use super::*;
+use std::ops::Range;
+
+// NOTE: what a highlighter carries from the end of a line to the start of the next one, e.g.
+//       being inside a string, each highlighter packs its own state, 0 is the start of the text
+pub type HighlightState = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    String,
+    Comment,
+    Lifetime,
+    Number,
+    Macro,
+}
+
+pub trait Highlighter {
+    // NOTE: the ranges are char indexes in the line, the line ends with its newline if it has one
+    fn highlight_line(
+        &self,
+        line: RopeSlice<'_>,
+        state: &mut HighlightState,
+        tokens: &mut Vec<(Range<usize>, TokenKind)>,
+    );
+}
+
+pub fn highlighter_for(path: &std::path::Path) -> Option<Box<dyn Highlighter>> {
+    match path.extension()?.to_str()? {
+        "rs" => Some(Box::new(RustHighlighter)),
+        _ => None,
+    }
+}
+
+// NOTE: the highlighting runs on the reassembled text so tokens can span blocks and wrapped
+//       lines, the state at the start of every line is cached until an edit before it
+pub(super) struct Highlight {
+    highlighter: Box<dyn Highlighter>,
+    states: Vec<HighlightState>,
+    lines: Range<usize>,
+    pub(super) tokens: Vec<(Point, Point, TokenKind)>,
+}
+
+impl Editor {
+    pub fn set_highlighter(&mut self, highlighter: Option<Box<dyn Highlighter>>) {
+        self.highlight = highlighter.map(|highlighter| Highlight {
+            highlighter,
+            states: vec![0],
+            lines: 0..0,
+            tokens: Vec::new(),
+        });
+        self.update_highlight();
+    }
+
+    pub(super) fn invalidate_highlight(&mut self, char_idx: usize) {
+        let line = self.text.char_to_line(char_idx.min(self.text.len_chars()));
+        if let Some(highlight) = &mut self.highlight {
+            highlight.states.truncate(line + 1);
+            highlight.lines = 0..0;
+            highlight.tokens.clear();
+        }
+    }
+
+    // NOTE: only the lines in the pane get tokens, the states before them are lexed once
+    pub(super) fn update_highlight(&mut self) {
+        let first = self.line_number(self.window.scroll());
+        let lines = first..(first + self.pane_height as usize).min(self.line_count());
+        let Some(highlight) = &mut self.highlight else {
+            return;
+        };
+        if highlight.lines == lines {
+            return;
+        }
+        highlight.tokens.clear();
+        let mut line = lines.start.min(highlight.states.len() - 1);
+        let mut state = highlight.states[line];
+        let mut line_tokens = Vec::new();
+        while line < lines.end {
+            line_tokens.clear();
+            highlight.highlighter.highlight_line(
+                self.text.line(line),
+                &mut state,
+                &mut line_tokens,
+            );
+            if line >= lines.start {
+                highlight
+                    .tokens
+                    .extend(line_tokens.drain(..).map(|(range, kind)| {
+                        (
+                            Point {
+                                line,
+                                column: range.start,
+                            },
+                            Point {
+                                line,
+                                column: range.end,
+                            },
+                            kind,
+                        )
+                    }));
+            }
+            line += 1;
+            if line == highlight.states.len() {
+                highlight.states.push(state);
+            }
+        }
+        highlight.lines = lines;
+    }
+
+    pub(super) fn tokens(&self) -> &[(Point, Point, TokenKind)] {
+        self.highlight
+            .as_ref()
+            .map_or(&[][..], |highlight| &highlight.tokens[..])
+    }
+}
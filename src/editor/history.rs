@@ -0,0 +1,564 @@
+use super::*;
+use std::io::{self, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub char_idx: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl Edit {
+    pub fn diff(old: &Rope, new: &Rope) -> Option<Self> {
+        let prefix = old
+            .chars()
+            .zip(new.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let max_suffix = old.len_chars().min(new.len_chars()) - prefix;
+        let suffix = old
+            .chars_at(old.len_chars())
+            .reversed()
+            .zip(new.chars_at(new.len_chars()).reversed())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let removed = old.slice(prefix..old.len_chars() - suffix);
+        let inserted = new.slice(prefix..new.len_chars() - suffix);
+        if removed.len_chars() == 0 && inserted.len_chars() == 0 {
+            return None;
+        }
+        Some(Self {
+            char_idx: prefix,
+            removed: removed.to_string(),
+            inserted: inserted.to_string(),
+        })
+    }
+
+    // NOTE: like `diff` for two versions of a piece of the text, the char index is in the piece
+    pub fn between(old: &str, new: &str) -> Option<Self> {
+        let prefix = old
+            .chars()
+            .zip(new.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(c, _)| c.len_utf8())
+            .sum::<usize>();
+        let suffix = old[prefix..]
+            .chars()
+            .rev()
+            .zip(new[prefix..].chars().rev())
+            .take_while(|(a, b)| a == b)
+            .map(|(c, _)| c.len_utf8())
+            .sum::<usize>();
+        let removed = &old[prefix..old.len() - suffix];
+        let inserted = &new[prefix..new.len() - suffix];
+        if removed.is_empty() && inserted.is_empty() {
+            return None;
+        }
+        Some(Self {
+            char_idx: old[..prefix].chars().count(),
+            removed: removed.to_string(),
+            inserted: inserted.to_string(),
+        })
+    }
+
+    pub fn apply(&self, rope: &mut Rope) {
+        let len_chars = self.removed.chars().count();
+        rope.remove(self.char_idx..self.char_idx + len_chars);
+        rope.insert(self.char_idx, &self.inserted);
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            char_idx: self.char_idx,
+            removed: self.inserted.clone(),
+            inserted: self.removed.clone(),
+        }
+    }
+
+    fn merge(&mut self, next: &Edit) -> bool {
+        if self.removed.is_empty()
+            && next.removed.is_empty()
+            && next.char_idx == self.char_idx + self.inserted.chars().count()
+        {
+            self.inserted.push_str(&next.inserted);
+            true
+        } else if self.inserted.is_empty()
+            && next.inserted.is_empty()
+            && next.char_idx + next.removed.chars().count() == self.char_idx
+        {
+            self.char_idx = next.char_idx;
+            self.removed.insert_str(0, &next.removed);
+            true
+        } else if self.inserted.is_empty()
+            && next.inserted.is_empty()
+            && next.char_idx == self.char_idx
+        {
+            self.removed.push_str(&next.removed);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum EditKind {
+    Insert,
+    Delete,
+    Other,
+}
+
+impl EditKind {
+    fn of(edit: &Edit) -> Self {
+        let is_char = |s: &str| {
+            let mut chars = s.chars();
+            chars.next().is_some_and(|c| c != '\n') && chars.next().is_none()
+        };
+        if edit.removed.is_empty() && is_char(&edit.inserted) {
+            Self::Insert
+        } else if edit.inserted.is_empty() && is_char(&edit.removed) {
+            Self::Delete
+        } else {
+            Self::Other
+        }
+    }
+}
+
+pub fn fnv1a<T: AsRef<[u8]>>(chunks: impl IntoIterator<Item = T>) -> u64 {
+    let mut hash = 0xcbf29ce484222325;
+    for chunk in chunks {
+        for &byte in chunk.as_ref() {
+            hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+// NOTE: nodes are never removed and always pushed at the end, so their index is also the order
+//       in which they were created
+#[derive(Debug, Clone)]
+struct Node {
+    parent: usize,
+    // the child redo goes to: the last one created or visited
+    child: Option<usize>,
+    edits: Vec<Edit>,
+    kind: EditKind,
+    before: WindowState,
+    after: WindowState,
+    time: u64,
+    // NOTE: the indentation before and after the step, for the steps that changed it
+    indentation: Option<(Indentation, Indentation)>,
+}
+
+#[derive(Debug)]
+pub struct History {
+    nodes: Vec<Node>,
+    current: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        let state = WindowState {
+            focus: None,
+            cursor: Point::default(),
+            cur_y: 0,
+        };
+        Self {
+            nodes: vec![Node {
+                parent: 0,
+                child: None,
+                edits: Vec::new(),
+                kind: EditKind::Other,
+                before: state,
+                after: state,
+                time: now(),
+                indentation: None,
+            }],
+            current: 0,
+        }
+    }
+}
+
+impl History {
+    // NOTE: the edits follow each other, the ones continuing the previous one are merged
+    pub fn record(&mut self, edits: Vec<Edit>, before: WindowState, after: WindowState) {
+        let mut merged = Vec::<Edit>::with_capacity(edits.len());
+        for edit in edits {
+            if !merged.last_mut().is_some_and(|last| last.merge(&edit)) {
+                merged.push(edit);
+            }
+        }
+        let kind = match &merged[..] {
+            [edit] => EditKind::of(edit),
+            _ => EditKind::Other,
+        };
+        self.push(merged, kind, before, after);
+    }
+
+    // NOTE: for edits made of many changes that should never be merged with typing
+    pub fn record_step(&mut self, edits: Vec<Edit>, before: WindowState, after: WindowState) {
+        self.push(edits, EditKind::Other, before, after);
+    }
+
+    // NOTE: a change of indentation with the edits it made to the expanded text
+    pub fn record_indentation(
+        &mut self,
+        edits: Vec<Edit>,
+        indentation: (Indentation, Indentation),
+        before: WindowState,
+        after: WindowState,
+    ) {
+        self.push(edits, EditKind::Other, before, after);
+        self.nodes[self.current].indentation = Some(indentation);
+    }
+
+    // NOTE: the one set by the closest step up to the current state, or the one before the first
+    //       change when none is on the way, `None` when it never changed
+    pub fn indentation(&self) -> Option<Indentation> {
+        let mut i = self.current;
+        while i != 0 {
+            if let Some((_, after)) = self.nodes[i].indentation {
+                return Some(after);
+            }
+            i = self.nodes[i].parent;
+        }
+        self.nodes
+            .iter()
+            .find_map(|node| node.indentation.map(|(before, _)| before))
+    }
+
+    // NOTE: the next edit starts a new step, even when it continues the typing of the last one
+    pub fn seal(&mut self) {
+        self.nodes[self.current].kind = EditKind::Other;
+    }
+
+    pub fn step_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // NOTE: the steps recorded since there were `count` of them become one, as long as nothing
+    //       was undone in between
+    pub fn squash(&mut self, count: usize) {
+        if self.nodes.len() <= count + 1
+            || self.current != self.nodes.len() - 1
+            || (count + 1..self.nodes.len()).any(|i| self.nodes[i].parent != i - 1)
+        {
+            return;
+        }
+        let merged = self.nodes.drain(count + 1..).collect::<Vec<_>>();
+        let first = &mut self.nodes[count];
+        for node in merged {
+            first.edits.extend(node.edits);
+            first.after = node.after;
+            first.time = node.time;
+        }
+        first.child = None;
+        first.kind = EditKind::Other;
+        self.current = count;
+    }
+
+    fn push(&mut self, edits: Vec<Edit>, kind: EditKind, before: WindowState, after: WindowState) {
+        let index = self.nodes.len();
+        let node = &mut self.nodes[self.current];
+        if self.current != 0
+            && self.current == index - 1
+            && node.child.is_none()
+            && kind != EditKind::Other
+            && node.kind == kind
+            && node.after == before
+        {
+            node.after = after;
+            node.time = now();
+            // NOTE: only a single edit has a kind other than `Other`
+            let last = node.edits.last_mut().unwrap();
+            if !last.merge(&edits[0]) {
+                node.edits.extend(edits);
+            }
+            return;
+        }
+        node.child = Some(index);
+        self.nodes.push(Node {
+            parent: self.current,
+            child: None,
+            edits,
+            kind,
+            before,
+            after,
+            time: now(),
+            indentation: None,
+        });
+        self.current = index;
+    }
+
+    pub fn undo(&mut self, edits: &mut Vec<Edit>) -> Option<WindowState> {
+        let parent = self.nodes[self.current].parent;
+        (self.current != 0).then(|| self.goto(parent, edits))?
+    }
+
+    pub fn redo(&mut self, edits: &mut Vec<Edit>) -> Option<WindowState> {
+        let child = self.nodes[self.current].child?;
+        self.goto(child, edits)
+    }
+
+    // Go to the state that was created just before the current one, whatever branch it is on.
+    pub fn earlier(&mut self, edits: &mut Vec<Edit>) -> Option<WindowState> {
+        self.goto(self.current.checked_sub(1)?, edits)
+    }
+
+    // Go to the state that was created just after the current one, whatever branch it is on.
+    pub fn later(&mut self, edits: &mut Vec<Edit>) -> Option<WindowState> {
+        (self.current + 1 < self.nodes.len()).then(|| self.goto(self.current + 1, edits))?
+    }
+
+    // Replace the current change by the next alternative made from the same state.
+    pub fn next_branch(&mut self, edits: &mut Vec<Edit>) -> Option<WindowState> {
+        let parent = self.nodes[self.current].parent;
+        let sibling = (self.current + 1..self.nodes.len())
+            .chain(1..self.current)
+            .find(|&i| self.nodes[i].parent == parent)?;
+        (self.current != 0).then(|| self.goto(sibling, edits))?
+    }
+
+    // Go to how the text looked `secs` seconds before (or after if negative) the current state.
+    pub fn travel(&mut self, secs: i64, edits: &mut Vec<Edit>) -> Option<WindowState> {
+        let time = self.nodes[self.current].time.saturating_add_signed(-secs);
+        let target = self
+            .nodes
+            .iter()
+            .rposition(|node| node.time <= time)
+            .unwrap_or(0);
+        self.goto(target, edits)
+    }
+
+    fn goto(&mut self, target: usize, edits: &mut Vec<Edit>) -> Option<WindowState> {
+        if target == self.current {
+            return None;
+        }
+        let mut path = vec![target];
+        while let Some(&last) = path.last()
+            && last != 0
+        {
+            path.push(self.nodes[last].parent);
+        }
+
+        let mut state = None;
+        let pos = loop {
+            if let Some(pos) = path.iter().position(|&i| i == self.current) {
+                break pos;
+            }
+            let node = &self.nodes[self.current];
+            edits.extend(node.edits.iter().rev().map(Edit::inverse));
+            state = Some(node.before);
+            let (child, parent) = (self.current, node.parent);
+            self.nodes[parent].child = Some(child);
+            self.current = parent;
+        };
+        for &i in path[..pos].iter().rev() {
+            let node = &self.nodes[i];
+            edits.extend(node.edits.iter().cloned());
+            state = Some(node.after);
+            self.nodes[self.current].child = Some(i);
+            self.current = i;
+        }
+        state
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        write_usize(writer, self.nodes.len())?;
+        write_usize(writer, self.current)?;
+        for node in &self.nodes {
+            write_usize(writer, node.parent)?;
+            write_usize(writer, node.child.map_or(0, |child| child))?;
+            writer.write_all(&[node.kind as u8])?;
+            write_state(writer, &node.before)?;
+            write_state(writer, &node.after)?;
+            writer.write_all(&node.time.to_le_bytes())?;
+            match node.indentation {
+                Some((before, after)) => {
+                    writer.write_all(&[1])?;
+                    write_indentation(writer, &before)?;
+                    write_indentation(writer, &after)?;
+                }
+                None => writer.write_all(&[0])?,
+            }
+            write_usize(writer, node.edits.len())?;
+            for edit in &node.edits {
+                write_usize(writer, edit.char_idx)?;
+                write_str(writer, &edit.removed)?;
+                write_str(writer, &edit.inserted)?;
+            }
+        }
+        Ok(())
+    }
+
+    // NOTE: `len_chars` is the length of the text at the current state, every edit has to fit in
+    //       the text it is applied to
+    pub fn read_from(reader: &mut impl Read, len_chars: usize) -> io::Result<Self> {
+        let len = read_usize(reader)?;
+        let current = read_usize(reader)?;
+        let mut nodes = Vec::new();
+        for i in 0..len {
+            let parent = read_usize(reader)?;
+            // NOTE: the root is the only node that can be a child of itself
+            let child = Some(read_usize(reader)?).filter(|&child| child != 0);
+            let mut kind = [0];
+            reader.read_exact(&mut kind)?;
+            let kind = match kind[0] {
+                0 => EditKind::Insert,
+                1 => EditKind::Delete,
+                2 => EditKind::Other,
+                _ => return Err(invalid_data()),
+            };
+            let before = read_state(reader)?;
+            let after = read_state(reader)?;
+            let mut time = [0; 8];
+            reader.read_exact(&mut time)?;
+            let mut changed = [0];
+            reader.read_exact(&mut changed)?;
+            let indentation = match changed[0] {
+                0 => None,
+                1 => Some((read_indentation(reader)?, read_indentation(reader)?)),
+                _ => return Err(invalid_data()),
+            };
+            let edits = (0..read_usize(reader)?)
+                .map(|_| {
+                    Ok(Edit {
+                        char_idx: read_usize(reader)?,
+                        removed: read_str(reader)?,
+                        inserted: read_str(reader)?,
+                    })
+                })
+                .collect::<io::Result<_>>()?;
+            if (i > 0 && parent >= i)
+                || parent >= len
+                || child.is_some_and(|child| child <= i || child >= len)
+            {
+                return Err(invalid_data());
+            }
+            nodes.push(Node {
+                parent,
+                child,
+                edits,
+                kind,
+                before,
+                after,
+                time: u64::from_le_bytes(time),
+                indentation,
+            });
+        }
+        if nodes.is_empty() || current >= nodes.len() || !nodes[0].edits.is_empty() {
+            return Err(invalid_data());
+        }
+        let history = Self { nodes, current };
+        if !history.fits(len_chars) {
+            return Err(invalid_data());
+        }
+        Ok(history)
+    }
+
+    // NOTE: the length of the text at each state follows from the one at the current state, the
+    //       parents come first
+    fn fits(&self, len_chars: usize) -> bool {
+        let growth = |edit: &Edit| {
+            edit.inserted.chars().count() as isize - edit.removed.chars().count() as isize
+        };
+        let mut offsets = vec![0isize; self.nodes.len()];
+        for (i, node) in self.nodes.iter().enumerate().skip(1) {
+            offsets[i] = offsets[node.parent] + node.edits.iter().map(growth).sum::<isize>();
+        }
+        let Some(root_len) = len_chars.checked_add_signed(-offsets[self.current]) else {
+            return false;
+        };
+        self.nodes.iter().skip(1).all(|node| {
+            let Some(mut len) = root_len.checked_add_signed(offsets[node.parent]) else {
+                return false;
+            };
+            node.edits.iter().all(|edit| {
+                let end = edit.char_idx.checked_add(edit.removed.chars().count());
+                if end.is_none_or(|end| end > len) {
+                    return false;
+                }
+                len = (len + edit.inserted.chars().count()) - edit.removed.chars().count();
+                true
+            })
+        })
+    }
+}
+
+fn invalid_data() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "corrupted undo history")
+}
+
+fn write_usize(writer: &mut impl Write, n: usize) -> io::Result<()> {
+    writer.write_all(&(n as u64).to_le_bytes())
+}
+
+fn read_usize(reader: &mut impl Read) -> io::Result<usize> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    usize::try_from(u64::from_le_bytes(bytes)).map_err(|_| invalid_data())
+}
+
+fn write_str(writer: &mut impl Write, s: &str) -> io::Result<()> {
+    write_usize(writer, s.len())?;
+    writer.write_all(s.as_bytes())
+}
+
+fn read_str(reader: &mut impl Read) -> io::Result<String> {
+    let len = read_usize(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|_| invalid_data())
+}
+
+fn write_state(writer: &mut impl Write, state: &WindowState) -> io::Result<()> {
+    let (line, indent) = state.focus.unwrap_or((usize::MAX, 0));
+    write_usize(writer, line)?;
+    write_usize(writer, indent)?;
+    write_usize(writer, state.cursor.line)?;
+    write_usize(writer, state.cursor.column)?;
+    write_usize(writer, state.cur_y as usize)
+}
+
+fn read_state(reader: &mut impl Read) -> io::Result<WindowState> {
+    let line = read_usize(reader)?;
+    let indent = read_usize(reader)?;
+    Ok(WindowState {
+        focus: (line != usize::MAX).then_some((line, indent)),
+        cursor: Point {
+            line: read_usize(reader)?,
+            column: read_usize(reader)?,
+        },
+        cur_y: read_usize(reader)?.try_into().map_err(|_| invalid_data())?,
+    })
+}
+
+fn write_indentation(writer: &mut impl Write, indentation: &Indentation) -> io::Result<()> {
+    write_usize(writer, indentation.width)?;
+    writer.write_all(&[indentation.tabs as u8])
+}
+
+fn read_indentation(reader: &mut impl Read) -> io::Result<Indentation> {
+    let width = read_usize(reader)?;
+    let mut tabs = [0];
+    reader.read_exact(&mut tabs)?;
+    if !(1..=indentation::MAX_INDENT_WIDTH).contains(&width) || tabs[0] > 1 {
+        return Err(invalid_data());
+    }
+    Ok(Indentation {
+        width,
+        tabs: tabs[0] == 1,
+    })
+}
@@ -0,0 +1,188 @@
+use super::*;
+use std::borrow::Cow;
+
+pub(super) const MAX_INDENT_WIDTH: usize = 8;
+
+// NOTE: leading tabs are expanded to `width` spaces when the text is loaded so the blocks can be
+//       found the same way, they are given back when the text is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Indentation {
+    pub width: usize,
+    pub tabs: bool,
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Self {
+            width: INDENT,
+            tabs: false,
+        }
+    }
+}
+
+impl std::fmt::Display for Indentation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.tabs {
+            write!(f, "Indent with tabs ({} columns)", self.width)
+        } else {
+            write!(f, "Indent with {} spaces", self.width)
+        }
+    }
+}
+
+impl Indentation {
+    // NOTE: the most common increase of indentation between two lines wins, a single space is
+    //       ignored because of the alignment in block comments
+    pub fn detect(text: &str) -> Self {
+        let mut tab_lines = 0;
+        let mut space_lines = 0;
+        let mut increases = [0usize; MAX_INDENT_WIDTH + 1];
+        let mut prev = 0;
+        for line in text.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line.starts_with('\t') {
+                tab_lines += 1;
+                continue;
+            }
+            let indent = line.chars().take_while(|c| *c == ' ').count();
+            if indent > 0 {
+                space_lines += 1;
+            }
+            if indent > prev && indent - prev <= MAX_INDENT_WIDTH {
+                increases[indent - prev] += 1;
+            }
+            prev = indent;
+        }
+        if tab_lines > space_lines {
+            return Self {
+                width: INDENT,
+                tabs: true,
+            };
+        }
+        let width = increases
+            .iter()
+            .enumerate()
+            .skip(2)
+            .filter(|(_, count)| **count > 0)
+            .max_by_key(|(width, count)| (**count, *width))
+            .map_or(INDENT, |(width, _)| width);
+        Self { width, tabs: false }
+    }
+
+    pub fn next_width(&self) -> Self {
+        Self {
+            width: match self.width {
+                2 => 4,
+                4 => 8,
+                _ => 2,
+            },
+            ..*self
+        }
+    }
+
+    pub fn expand<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !self.tabs || !text.contains('\t') {
+            return Cow::Borrowed(text);
+        }
+        Cow::Owned(
+            text.split_inclusive('\n')
+                .map(|line| {
+                    let leading = line.len() - line.trim_start_matches([' ', '\t']).len();
+                    let (indent, rest) = line.split_at(leading);
+                    let mut expanded = indent.replace('\t', &HSPACES[..self.width]);
+                    expanded.push_str(rest);
+                    expanded
+                })
+                .collect(),
+        )
+    }
+
+    // NOTE: by line of the text, the leading whitespace that collapsing its expansion would not
+    //       give back
+    pub fn file_indents(&self, text: &str) -> Vec<Option<Box<str>>> {
+        if !self.tabs {
+            return Vec::new();
+        }
+        let indents = text
+            .split_inclusive('\n')
+            .map(|line| {
+                let leading = line.len() - line.trim_start_matches([' ', '\t']).len();
+                let expanded = self.expand(line);
+                (self.collapse(&expanded) != line).then(|| line[..leading].into())
+            })
+            .collect::<Vec<_>>();
+        if indents.iter().all(Option::is_none) {
+            return Vec::new();
+        }
+        indents
+    }
+
+    pub fn collapse<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let leading = line.chars().take_while(|c| *c == ' ').count();
+        if !self.tabs || leading < self.width {
+            return Cow::Borrowed(line);
+        }
+        let mut collapsed = "\t".repeat(leading / self.width);
+        collapsed.push_str(&line[leading - leading % self.width..]);
+        Cow::Owned(collapsed)
+    }
+}
+
+impl Editor {
+    #[inline(always)]
+    pub fn indentation(&self) -> Indentation {
+        self.indentation
+    }
+
+    // NOTE: the lines as they are written, the ones not edited keep the whitespace they had
+    pub(super) fn file_lines(&self) -> impl Iterator<Item = String> {
+        self.text.lines().enumerate().map(|(i, line)| {
+            let line = line.to_string();
+            match self.file_indents.get(i) {
+                Some(Some(indent)) => format!("{indent}{}", line.trim_start_matches(' ')),
+                _ => self.indentation.collapse(&line).into_owned(),
+            }
+        })
+    }
+
+    // NOTE: the whitespace of the file is only kept while the tabs are
+    pub(super) fn change_indentation(&mut self, indentation: Indentation) {
+        if indentation.tabs != self.indentation.tabs {
+            self.file_indents.clear();
+        }
+        self.indentation = indentation;
+    }
+
+    // NOTE: one step of the undo history, with the edits of the leading spaces when the tabs have
+    //       to be expanded again to another width
+    pub fn set_indentation(&mut self, indentation: Indentation) -> bool {
+        if indentation == self.indentation {
+            return false;
+        }
+        let text = if self.indentation.tabs && indentation.tabs {
+            Rope::from_str(&indentation.expand(&self.file_lines().collect::<String>()))
+        } else {
+            self.text.clone()
+        };
+        let mut edits = Vec::new();
+        for (i, (old, new)) in self.text.lines().zip(text.lines()).enumerate() {
+            if old != new
+                && let Some(mut edit) = Edit::between(&old.to_string(), &new.to_string())
+            {
+                edit.char_idx += text.line_to_char(i);
+                edits.push(edit);
+            }
+        }
+        let before = self.window_state();
+        let change = (self.indentation, indentation);
+        self.change_indentation(indentation);
+        self.anchor = None;
+        self.reload(text, &before);
+        self.history
+            .record_indentation(edits, change, before, self.window_state());
+        self.refresh_search();
+        true
+    }
+}
@@ -0,0 +1,380 @@
+use super::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCode {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    BackTab,
+    Esc,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    F(u8),
+}
+
+// NOTE: the shift of a char is in its case, Shift is only kept for the other keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Keystroke {
+    pub code: KeyCode,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+static NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("Enter", KeyCode::Enter),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Tab", KeyCode::Tab),
+    ("BackTab", KeyCode::BackTab),
+    ("Esc", KeyCode::Esc),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Space", KeyCode::Char(' ')),
+];
+
+impl Keystroke {
+    pub fn new(code: KeyCode, ctrl: bool, alt: bool, shift: bool) -> Self {
+        match code {
+            KeyCode::Char(c) if shift => Self {
+                code: KeyCode::Char(c.to_uppercase().next().unwrap_or(c)),
+                ctrl,
+                alt,
+                shift: false,
+            },
+            _ => Self {
+                code,
+                ctrl,
+                alt,
+                shift,
+            },
+        }
+    }
+
+    // NOTE: the char typed by this key, if it has no modifier
+    pub fn typed(&self) -> Option<char> {
+        match self.code {
+            KeyCode::Char(c) if !self.ctrl && !self.alt => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Keystroke {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ctrl {
+            f.write_str("Ctrl-")?;
+        }
+        if self.alt {
+            f.write_str("Alt-")?;
+        }
+        match self.code {
+            // NOTE: Ctrl-x is written Ctrl-X like everywhere else, Ctrl-X is Ctrl-Shift-X
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(c) if self.ctrl && c.is_uppercase() => write!(f, "Shift-{c}"),
+            KeyCode::Char(c) if self.ctrl => write!(f, "{}", c.to_uppercase()),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => {
+                if self.shift {
+                    f.write_str("Shift-")?;
+                }
+                write!(f, "F{n}")
+            }
+            code => {
+                if self.shift {
+                    f.write_str("Shift-")?;
+                }
+                let (name, _) = NAMED_KEYS.iter().find(|(_, named)| *named == code).unwrap();
+                f.write_str(name)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Keystroke {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (mut ctrl, mut alt, mut shift) = (false, false, false);
+        let mut rest = s;
+        // NOTE: the last part is the key itself, so Alt-- is Alt and -
+        while let Some((modifier, tail)) = rest.split_once('-')
+            && !tail.is_empty()
+        {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "c" => ctrl = true,
+                "alt" | "m" => alt = true,
+                "shift" | "s" => shift = true,
+                _ => return Err(format!("unknown modifier in {s}")),
+            }
+            rest = tail;
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if ctrl && !shift => KeyCode::Char(c.to_ascii_lowercase()),
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                if let Some(n) = rest.strip_prefix(['F', 'f'])
+                    && let Ok(n) = n.parse()
+                {
+                    KeyCode::F(n)
+                } else {
+                    NAMED_KEYS
+                        .iter()
+                        .find(|(name, _)| name.eq_ignore_ascii_case(rest))
+                        .map(|(_, code)| *code)
+                        .ok_or_else(|| format!("unknown key: {s}"))?
+                }
+            }
+        };
+        Ok(Self::new(code, ctrl, alt, shift))
+    }
+}
+
+pub fn format_keys(keys: &[Keystroke]) -> String {
+    keys.iter()
+        .map(Keystroke::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+static DEFAULT_KEYS: &str = r##"
+"Enter" = "InsertNewline"
+"Backspace" = "DeleteBackward"
+"Ctrl-H" = "DeleteBackward"
+"Delete" = "DeleteForward"
+"Up" = "MoveCursorUp"
+"Down" = "MoveCursorDown"
+"Left" = "MoveCursorLeft"
+"Right" = "MoveCursorRight"
+"Shift-Up" = "SelectUp"
+"Shift-Down" = "SelectDown"
+"Shift-Left" = "SelectLeft"
+"Shift-Right" = "SelectRight"
+"Ctrl-Up" = "ScrollUp"
+"Ctrl-Down" = "ScrollDown"
+"Ctrl-Alt-Up" = "AddCursorAbove"
+"Ctrl-Alt-Down" = "AddCursorBelow"
+"Alt-d" = "AddCursorAtNextMatch"
+"PageUp" = "PageUp"
+"PageDown" = "PageDown"
+"Shift-PageUp" = "PageUp"
+"Shift-PageDown" = "PageDown"
+"Ctrl-PageUp" = "PageUp"
+"Ctrl-PageDown" = "PageDown"
+"Home" = "MoveCursorAtStart"
+"Shift-Home" = "SelectToStart"
+"End" = "MoveCursorAtEnd"
+"Shift-End" = "SelectToEnd"
+"Alt-Home" = "MoveCursorAt0"
+"Tab" = "IndentSelectionOrNextPane"
+"BackTab" = "DedentSelection"
+"Shift-BackTab" = "DedentSelection"
+"Esc" = "Cancel"
+"F3" = "SearchNext"
+"Shift-F3" = "SearchPrev"
+"Ctrl-Z" = "Undo"
+"Ctrl-Y" = "Redo"
+"Alt-z" = "UndoEarlier"
+"Alt-y" = "UndoLater"
+"Alt-b" = "UndoNextBranch"
+"Alt-t" = "UndoTravel"
+"F7" = "SetWindowToParent"
+"F8" = "SetWindowToCursor"
+"F6" = "RootWindow"
+"Shift-F7" = "PickAncestor"
+"Alt-Left" = "WindowBack"
+"Alt-Right" = "WindowForward"
+"Ctrl-W" = "NextPane"
+"Ctrl-Q" = "Quit"
+"Ctrl-S" = "Save"
+"Ctrl-C" = "Copy"
+"Ctrl-X" = "Cut"
+"Ctrl-V" = "Paste"
+"Alt-v" = "PasteNext"
+"Alt-p" = "KillRing"
+"Alt-w" = "CycleWrap"
+"Alt-=" = "WidenWrap"
+"Alt--" = "NarrowWrap"
+"Alt-W" = "ToggleWordWrap"
+"Alt-i" = "NextIndentWidth"
+"Alt-I" = "ToggleTabs"
+"Alt-n" = "ToggleRelativeNumbers"
+"Alt-m" = "ToggleVim"
+"Ctrl-G" = "GotoLine"
+"Ctrl-F" = "Search"
+"Ctrl-R" = "Replace"
+"Ctrl-D" = "ToggleDebug"
+"Alt-PageUp" = "DebugScrollUp"
+"Alt-PageDown" = "DebugScrollDown"
+"##;
+
+// NOTE: applied over the default keys, what it does not rebind keeps working
+static EMACS_KEYS: &str = r##"
+"Ctrl-A" = "MoveCursorAtStart"
+"Ctrl-E" = "MoveCursorAtEnd"
+"Ctrl-F" = "MoveCursorRight"
+"Ctrl-B" = "MoveCursorLeft"
+"Ctrl-N" = "MoveCursorDown"
+"Ctrl-P" = "MoveCursorUp"
+"Alt-f" = "MoveWordForward"
+"Alt-b" = "MoveWordBackward"
+"Ctrl-V" = "PageDown"
+"Alt-v" = "PageUp"
+"Ctrl-D" = "DeleteForward"
+"Ctrl-K" = "KillLine"
+"Ctrl-W" = "Cut"
+"Alt-w" = "Copy"
+"Ctrl-Y" = "Paste"
+"Alt-y" = "PasteNext"
+"Ctrl-Space" = "SetMark"
+"Ctrl-G" = "Cancel"
+"Ctrl-S" = "Search"
+"Alt-%" = "Replace"
+"Alt-g g" = "GotoLine"
+"Ctrl-X Ctrl-S" = "Save"
+"Ctrl-X Ctrl-C" = "Quit"
+"Ctrl-X u" = "Undo"
+"Ctrl-X o" = "NextPane"
+"Ctrl-X h" = "RootWindow"
+"##;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolved {
+    Action(Action),
+    // NOTE: the keys so far start one or more chords
+    Pending(Vec<Keystroke>),
+    Unbound(Vec<Keystroke>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Vec<Keystroke>, Action>,
+    pending: Vec<Keystroke>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+            pending: Vec::new(),
+        };
+        keymap.load(DEFAULT_KEYS).unwrap();
+        keymap
+    }
+}
+
+impl Keymap {
+    // NOTE: one binding per line, `"Ctrl-X Ctrl-S" = "Save"`, the keys of a chord are separated
+    //       by spaces and the action `Unbound` removes a default binding, `preset = "emacs"`
+    //       brings the bindings of a preset in at that point
+    pub fn load(&mut self, text: &str) -> std::result::Result<(), String> {
+        for (i, line) in text.lines().enumerate() {
+            let error = |message: String| format!("line {}: {message}", i + 1);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keys, value) = match line.strip_prefix('"') {
+                Some(rest) => {
+                    let (keys, value) = rest
+                        .split_once('"')
+                        .ok_or_else(|| error("expected \"".to_string()))?;
+                    (keys, value.trim_start())
+                }
+                None => line.split_at(line.find([' ', '=']).unwrap_or(line.len())),
+            };
+            let value = value
+                .trim_start()
+                .strip_prefix('=')
+                .ok_or_else(|| error("expected keys = action".to_string()))?;
+            let value = value.split('#').next().unwrap().trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            if keys.trim() == "preset" {
+                self.preset(value).map_err(error)?;
+                continue;
+            }
+            let keys = keys
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<std::result::Result<Vec<Keystroke>, _>>()
+                .map_err(error)?;
+            if keys.is_empty() {
+                return Err(error("expected keys".to_string()));
+            }
+            self.bindings.remove(&keys);
+            if value != "Unbound" {
+                let action = value.parse().map_err(error)?;
+                // NOTE: a key cannot both do something and start a chord, the last binding wins
+                self.bindings
+                    .retain(|bound, _| !bound.starts_with(&keys) && !keys.starts_with(bound));
+                self.bindings.insert(keys, action);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn preset(&mut self, name: &str) -> std::result::Result<(), String> {
+        match name {
+            "default" => {
+                self.bindings.clear();
+                self.load(DEFAULT_KEYS)
+            }
+            "emacs" => self.load(EMACS_KEYS),
+            _ => Err(format!("unknown preset: {name} (expected default, emacs)")),
+        }
+    }
+
+    // NOTE: the action of a whole binding, without touching the keys being typed
+    pub fn lookup(&self, keys: &[Keystroke]) -> Option<Action> {
+        self.bindings.get(keys).copied()
+    }
+
+    pub fn feed(&mut self, key: Keystroke) -> Resolved {
+        self.pending.push(key);
+        if let Some(action) = self.bindings.get(&self.pending) {
+            self.pending.clear();
+            return Resolved::Action(*action);
+        }
+        let pending = &self.pending;
+        if self
+            .bindings
+            .keys()
+            .any(|keys| keys.len() > pending.len() && keys.starts_with(pending))
+        {
+            Resolved::Pending(self.pending.clone())
+        } else {
+            Resolved::Unbound(std::mem::take(&mut self.pending))
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn keys_for(&self, action: Action) -> Option<Vec<Keystroke>> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(keys, _)| keys.clone())
+            .min_by_key(|keys| (keys.len(), format_keys(keys)))
+    }
+}
@@ -0,0 +1,200 @@
+use super::*;
+use std::collections::VecDeque;
+
+const KILL_RING_SIZE: usize = 32;
+
+// NOTE: entries are stored without their common indentation, it is given back when pasting
+#[derive(Debug, Default)]
+pub struct KillRing {
+    entries: VecDeque<String>,
+}
+
+impl KillRing {
+    pub fn push(&mut self, text: String) {
+        if let Some(i) = self.entries.iter().position(|entry| *entry == text) {
+            self.entries.remove(i);
+        }
+        self.entries.push_front(text);
+        self.entries.truncate(KILL_RING_SIZE);
+    }
+
+    // NOTE: kills in a row make a single entry
+    pub fn append(&mut self, text: &str) {
+        match self.entries.front_mut() {
+            Some(entry) => entry.push_str(text),
+            None => self.entries.push_front(text.to_string()),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(String::as_str)
+    }
+
+    pub fn promote(&mut self, index: usize) {
+        if let Some(entry) = self.entries.remove(index) {
+            self.entries.push_front(entry);
+        }
+    }
+}
+
+fn leading_spaces(s: &str) -> usize {
+    s.chars().take_while(|c| *c == ' ').count()
+}
+
+impl Editor {
+    pub fn copy(&mut self, kill_ring: &mut KillRing) -> bool {
+        let Some(text) = self.selected_text() else {
+            return false;
+        };
+        kill_ring.push(text);
+        self.clear_selection()
+    }
+
+    pub fn cut(&mut self, kill_ring: &mut KillRing) -> bool {
+        let Some(text) = self.selected_text() else {
+            return false;
+        };
+        kill_ring.push(text);
+        self.replace_selection("")
+    }
+
+    // NOTE: kills the rest of the line, or the newline when only blanks are left. It is added to
+    //       the last entry when the previous action was a kill too
+    pub fn kill_line(&mut self, kill_ring: &mut KillRing, append: bool) -> bool {
+        self.anchor = None;
+        let start = self.clamp_point(self.cursor_point());
+        let line = self.text.line(start.line);
+        let rest = line.slice(start.column..);
+        let end = if is_blank(rest) {
+            Point {
+                line: start.line + 1,
+                column: 0,
+            }
+        } else {
+            Point {
+                column: self
+                    .clamp_point(Point {
+                        column: usize::MAX,
+                        ..start
+                    })
+                    .column,
+                ..start
+            }
+        };
+        let text = self
+            .text
+            .slice(self.point_to_char(start)..self.point_to_char(end))
+            .to_string();
+        if text.is_empty() {
+            return false;
+        }
+        if append {
+            kill_ring.append(&text);
+        } else {
+            kill_ring.push(text);
+        }
+        self.replace_range(start, end, "")
+    }
+
+    pub fn paste(&mut self, kill_ring: &KillRing, index: usize) -> bool {
+        let Some(text) = kill_ring.get(index) else {
+            return false;
+        };
+        let (start, end) = self.selection().unwrap_or_else(|| {
+            let cursor = self.cursor_point();
+            (cursor, cursor)
+        });
+        self.anchor = None;
+        let start = self.clamp_point(start);
+        let text = self.reindent_for(start, text);
+        if !self.replace_range(start, end, &text) {
+            return false;
+        }
+        self.last_paste = Some((start, self.cursor_point(), index));
+        true
+    }
+
+    pub fn paste_next(&mut self, kill_ring: &KillRing) -> bool {
+        let Some((start, end, index)) = self.last_paste.take() else {
+            return self.paste(kill_ring, 0);
+        };
+        if self.cursor_point() != end || kill_ring.is_empty() {
+            return self.paste(kill_ring, 0);
+        }
+        let index = (index + 1) % kill_ring.len();
+        let text = self.reindent_for(start, kill_ring.get(index).unwrap());
+        if !self.replace_range(start, end, &text) {
+            return false;
+        }
+        self.last_paste = Some((start, self.cursor_point(), index));
+        true
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.selection()?;
+        Some(self.text_between(start, end))
+    }
+
+    pub(super) fn text_between(&self, start: Point, end: Point) -> String {
+        let start = self.clamp_point(start);
+        let text = self
+            .text
+            .slice(self.point_to_char(start)..self.point_to_char(end))
+            .to_string();
+        let first_indent = leading_spaces(&self.text.line(start.line).to_string());
+        let lines = text.split_inclusive('\n').collect::<Vec<_>>();
+        // NOTE: the first line only counts if the selection starts in its indentation
+        let base = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| match i {
+                0 => (start.column <= first_indent).then_some(first_indent),
+                _ => (!is_blank(RopeSlice::from(*line))).then(|| leading_spaces(line)),
+            })
+            .min()
+            .unwrap_or(0);
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let strip = match i {
+                    0 => base.saturating_sub(start.column).min(leading_spaces(line)),
+                    _ => base.min(leading_spaces(line)),
+                };
+                &line[strip..]
+            })
+            .collect()
+    }
+
+    fn reindent_for(&self, at: Point, text: &str) -> String {
+        let line = self.text.line(at.line);
+        let line_indent = leading_spaces(&line.to_string());
+        let indent = if is_blank(line) || at.column <= line_indent {
+            at.column
+        } else {
+            line_indent
+        };
+        text.split_inclusive('\n')
+            .enumerate()
+            .map(|(i, line)| {
+                if i == 0 || is_blank(RopeSlice::from(line)) {
+                    line.to_string()
+                } else {
+                    format!("{}{}", &HSPACES[..indent.min(HSPACES.len())], line)
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,60 @@
+use super::*;
+
+// NOTE: the states are logical like the ones of the undo history, they survive the blocks being
+//       split or reloaded, and their lines follow the edits made since they were left
+const WINDOW_HISTORY: usize = 100;
+
+impl Editor {
+    // NOTE: a change of focus asked for can be gone back from, the states gone forward to are
+    //       forgotten
+    pub(super) fn focus(&mut self, change: impl FnOnce(&mut Self) -> bool) -> bool {
+        let before = self.window_state();
+        if !change(self) {
+            return false;
+        }
+        if self.window_state() != before {
+            if self.window_back.len() == WINDOW_HISTORY {
+                self.window_back.remove(0);
+            }
+            self.window_back.push(before);
+            self.window_forward.clear();
+        }
+        true
+    }
+
+    pub fn window_back(&mut self) -> bool {
+        let Some(state) = self.window_back.pop() else {
+            return false;
+        };
+        self.window_forward.push(self.window_state());
+        self.restore_window_state(&state);
+        true
+    }
+
+    pub fn window_forward(&mut self) -> bool {
+        let Some(state) = self.window_forward.pop() else {
+            return false;
+        };
+        self.window_back.push(self.window_state());
+        self.restore_window_state(&state);
+        true
+    }
+
+    // NOTE: called before the text changes, a line removed by the edit moves to where it ends
+    pub(super) fn shift_window_history(&mut self, edit: &Edit) {
+        let line = self.text.char_to_line(edit.char_idx);
+        let removed = edit.removed.matches('\n').count();
+        let inserted = edit.inserted.matches('\n').count();
+        let shift = |l: &mut usize| {
+            if *l > line {
+                *l = (*l).max(line + removed) - removed + inserted;
+            }
+        };
+        for state in self.window_back.iter_mut().chain(&mut self.window_forward) {
+            if let Some((focus, _)) = &mut state.focus {
+                shift(focus);
+            }
+            shift(&mut state.cursor.line);
+        }
+    }
+}
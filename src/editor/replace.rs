@@ -0,0 +1,287 @@
+use super::*;
+use regex::{Captures, Regex};
+use std::ops::Range;
+
+// NOTE: the matches are searched in the text as it was when the replace started, forward from
+//       the end of the previous one, and each replacement is an edit made through the buffer of
+//       its lines at the match shifted by the replacements before it. The whole replace is
+//       recorded as a single step
+#[derive(Debug)]
+pub struct Replace {
+    regex: Regex,
+    replacement: String,
+    expand: bool,
+    text: String,
+    original: Rope,
+    // the chars the replacements before the current match moved it by
+    shift: isize,
+    edits: Vec<Edit>,
+    before: WindowState,
+    // where the query started, it ends there after wrapping around
+    start: usize,
+    from: usize,
+    wrapped: bool,
+    current: Option<Range<usize>>,
+    count: usize,
+}
+
+impl Replace {
+    fn expand(&self, caps: &Captures) -> String {
+        if self.expand {
+            let mut dst = String::new();
+            caps.expand(&self.replacement, &mut dst);
+            dst
+        } else {
+            self.replacement.clone()
+        }
+    }
+
+    // NOTE: the edit replacing the current match, the next one is searched from its end
+    fn take_edit(&mut self) -> Option<Edit> {
+        let range = self.current.take()?;
+        let caps = self.regex.captures_at(&self.text, range.start)?;
+        let edit = match_edit(
+            &self.original,
+            &self.text,
+            range.clone(),
+            self.shift,
+            self.expand(&caps),
+        );
+        self.shift += edit_shift(&edit);
+        self.from = range.end;
+        self.count += 1;
+        Some(edit)
+    }
+}
+
+fn match_edit(
+    original: &Rope,
+    text: &str,
+    range: Range<usize>,
+    shift: isize,
+    with: String,
+) -> Edit {
+    Edit {
+        char_idx: original
+            .byte_to_char(range.start)
+            .saturating_add_signed(shift),
+        removed: text[range].to_string(),
+        inserted: with,
+    }
+}
+
+// NOTE: the newline ending the text is not searched, it cannot be replaced
+fn searched_text(text: &Rope) -> String {
+    let mut text = text.to_string();
+    text.pop();
+    text
+}
+
+fn edit_shift(edit: &Edit) -> isize {
+    edit.inserted.chars().count() as isize - edit.removed.chars().count() as isize
+}
+
+impl Editor {
+    pub fn replace_all(
+        &mut self,
+        pattern: &str,
+        replacement: &str,
+        options: SearchOptions,
+    ) -> std::result::Result<usize, String> {
+        let regex = build_regex(pattern, options)?;
+        let text = searched_text(&self.text);
+        let before = self.window_state();
+        let mut shift = 0;
+        let mut edits = Vec::new();
+        for caps in regex.captures_iter(&text) {
+            let matched = caps.get(0).unwrap();
+            if matched.is_empty() {
+                continue;
+            }
+            let with = if options.regex {
+                let mut dst = String::new();
+                caps.expand(replacement, &mut dst);
+                dst
+            } else {
+                replacement.to_string()
+            };
+            let edit = match_edit(&self.text, &text, matched.range(), shift, with);
+            shift += edit_shift(&edit);
+            edits.push(edit);
+        }
+        let count = edits.len();
+        if count == 0 {
+            return Ok(0);
+        }
+        self.edit_text(&edits);
+        let edits = std::mem::take(&mut self.edits);
+        self.reset_window(&before);
+        let after = self.window_state();
+        self.history.record_step(edits, before, after);
+        self.refresh_search();
+        Ok(count)
+    }
+
+    pub fn start_replace(
+        &mut self,
+        pattern: &str,
+        replacement: &str,
+        options: SearchOptions,
+    ) -> std::result::Result<bool, String> {
+        let regex = build_regex(pattern, options)?;
+        let (line, column) = {
+            let cursor = self.cursor_point();
+            (cursor.line.min(self.text.len_lines() - 1), cursor.column)
+        };
+        let line_start = self.text.line_to_char(line);
+        let line_len = self.text.line(line).len_chars();
+        let start = self
+            .text
+            .char_to_byte(line_start + column.min(line_len.saturating_sub(1)));
+        self.anchor = None;
+        self.start_search();
+        self.set_search_regex(regex.clone());
+        self.replace = Some(Replace {
+            regex,
+            replacement: replacement.to_string(),
+            expand: options.regex,
+            text: searched_text(&self.text),
+            original: self.text.clone(),
+            shift: 0,
+            edits: Vec::new(),
+            before: self.window_state(),
+            start,
+            from: start,
+            wrapped: false,
+            current: None,
+            count: 0,
+        });
+        if self.find_next_replace() {
+            Ok(true)
+        } else {
+            self.finish_replace();
+            Ok(false)
+        }
+    }
+
+    pub fn replace_preview(&self) -> Option<(String, String)> {
+        let replace = self.replace.as_ref()?;
+        let range = replace.current.clone()?;
+        let caps = replace.regex.captures_at(&replace.text, range.start)?;
+        Some((replace.text[range].to_string(), replace.expand(&caps)))
+    }
+
+    pub fn replace_current(&mut self) -> bool {
+        let Some(edit) = self.replace.as_mut().and_then(Replace::take_edit) else {
+            return false;
+        };
+        let state = self.window_state();
+        let cursor = self.replace_edits(vec![edit]);
+        let state = WindowState { cursor, ..state };
+        self.reset_window(&state);
+        self.refresh_search();
+        self.find_next_replace()
+    }
+
+    pub fn skip_current(&mut self) -> bool {
+        let Some(replace) = &mut self.replace else {
+            return false;
+        };
+        let Some(range) = replace.current.take() else {
+            return false;
+        };
+        replace.from = range.end;
+        self.find_next_replace()
+    }
+
+    // NOTE: the cursor is only moved after the last replacement
+    pub fn replace_remaining(&mut self) -> bool {
+        let mut edits = Vec::new();
+        while let Some(edit) = self.replace.as_mut().and_then(Replace::take_edit) {
+            edits.push(edit);
+            if !self.find_next_match() {
+                break;
+            }
+        }
+        if edits.is_empty() {
+            return false;
+        }
+        let state = self.window_state();
+        let cursor = self.replace_edits(edits);
+        let state = WindowState { cursor, ..state };
+        self.reset_window(&state);
+        self.refresh_search();
+        true
+    }
+
+    pub fn finish_replace(&mut self) -> usize {
+        let Some(replace) = self.replace.take() else {
+            return 0;
+        };
+        self.clear_search();
+        if !replace.edits.is_empty() {
+            let after = self.window_state();
+            self.history
+                .record_step(replace.edits, replace.before, after);
+        }
+        replace.count
+    }
+
+    // NOTE: the point after the last replacement is returned, the window is left to be set again
+    //       from a state taken before
+    fn replace_edits(&mut self, edits: Vec<Edit>) -> Point {
+        let last = &edits[edits.len() - 1];
+        let end = last.char_idx + last.inserted.chars().count();
+        self.edit_text(&edits);
+        let replace = self.replace.as_mut().unwrap();
+        replace.edits.append(&mut self.edits);
+        self.char_to_point(end)
+    }
+
+    fn find_next_replace(&mut self) -> bool {
+        if !self.find_next_match() {
+            return false;
+        }
+        let replace = self.replace.as_ref().unwrap();
+        let start = replace.current.as_ref().unwrap().start;
+        let start = replace.original.byte_to_char(start);
+        let point = self.char_to_point(start.saturating_add_signed(replace.shift));
+        self.set_cursor_point(point, self.pane_height / 2);
+        true
+    }
+
+    // NOTE: after wrapping around, the replacements made are all after the matches left so they
+    //       are not shifted anymore, and a match reaching the start is not made
+    fn find_next_match(&mut self) -> bool {
+        let Some(replace) = &mut self.replace else {
+            return false;
+        };
+        let text = &replace.text;
+        let found = loop {
+            let found = if replace.from <= text.len() {
+                replace.regex.find_at(text, replace.from)
+            } else {
+                None
+            };
+            match found {
+                Some(m) if m.is_empty() => {
+                    replace.from = text[m.end()..]
+                        .chars()
+                        .next()
+                        .map_or(usize::MAX, |c| m.end() + c.len_utf8());
+                }
+                Some(m) if !(replace.wrapped && m.end() > replace.start) => {
+                    break Some(m.range());
+                }
+                None if !replace.wrapped => {
+                    replace.wrapped = true;
+                    replace.shift = 0;
+                    replace.from = 0;
+                }
+                _ => break None,
+            }
+        };
+        replace.current = found;
+        replace.current.is_some()
+    }
+}
@@ -0,0 +1,280 @@
+use super::*;
+use std::ops::Range;
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "yield",
+];
+
+// NOTE: the tokens that can span lines, packed in a HighlightState as a tag and a count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LexState {
+    Code,
+    BlockComment(u64),
+    String,
+    RawString(u64),
+}
+
+impl LexState {
+    fn pack(self) -> HighlightState {
+        match self {
+            Self::Code => 0,
+            Self::BlockComment(depth) => depth << 2 | 1,
+            Self::String => 2,
+            Self::RawString(hashes) => hashes << 2 | 3,
+        }
+    }
+
+    fn unpack(state: HighlightState) -> Self {
+        match state & 3 {
+            1 => Self::BlockComment(state >> 2),
+            2 => Self::String,
+            3 => Self::RawString(state >> 2),
+            _ => Self::Code,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RustHighlighter;
+
+impl Highlighter for RustHighlighter {
+    fn highlight_line(
+        &self,
+        line: RopeSlice<'_>,
+        state: &mut HighlightState,
+        tokens: &mut Vec<(Range<usize>, TokenKind)>,
+    ) {
+        let chars: Vec<char> = line.chars().take_while(|c| *c != '\n').collect();
+        let mut lexer = Lexer {
+            chars: &chars,
+            at: 0,
+            state: LexState::unpack(*state),
+        };
+        while let Some((range, kind)) = lexer.next_token() {
+            if range.start < range.end {
+                tokens.push((range, kind));
+            }
+        }
+        *state = lexer.state.pack();
+    }
+}
+
+struct Lexer<'c> {
+    chars: &'c [char],
+    at: usize,
+    state: LexState,
+}
+
+impl Lexer<'_> {
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.at + offset).copied()
+    }
+
+    fn eat_while(&mut self, f: impl Fn(char) -> bool) {
+        while self.peek(0).is_some_and(&f) {
+            self.at += 1;
+        }
+    }
+
+    fn next_token(&mut self) -> Option<(Range<usize>, TokenKind)> {
+        if self.at >= self.chars.len() {
+            return None;
+        }
+        let start = self.at;
+        match self.state {
+            LexState::BlockComment(depth) => {
+                self.block_comment(depth);
+                return Some((start..self.at, TokenKind::Comment));
+            }
+            LexState::String => {
+                self.string();
+                return Some((start..self.at, TokenKind::String));
+            }
+            LexState::RawString(hashes) => {
+                self.raw_string(hashes);
+                return Some((start..self.at, TokenKind::String));
+            }
+            LexState::Code => {}
+        }
+        loop {
+            let start = self.at;
+            let c = self.peek(0)?;
+            let kind = match c {
+                '/' if self.peek(1) == Some('/') => {
+                    self.at = self.chars.len();
+                    TokenKind::Comment
+                }
+                '/' if self.peek(1) == Some('*') => {
+                    self.at += 2;
+                    self.block_comment(1);
+                    TokenKind::Comment
+                }
+                '"' => {
+                    self.at += 1;
+                    self.string();
+                    TokenKind::String
+                }
+                '\'' => match self.quote() {
+                    Some(kind) => kind,
+                    None => continue,
+                },
+                'b' | 'c' | 'r' if self.string_prefix() => TokenKind::String,
+                '0'..='9' => {
+                    self.number();
+                    TokenKind::Number
+                }
+                c if c == '_' || c.is_alphabetic() => {
+                    let raw = c == 'r' && self.peek(1) == Some('#');
+                    if raw {
+                        self.at += 2;
+                    }
+                    self.eat_while(|c| c == '_' || c.is_alphanumeric());
+                    let word: String = self.chars[start..self.at].iter().collect();
+                    if self.peek(0) == Some('!') && self.peek(1) != Some('=') {
+                        self.at += 1;
+                        TokenKind::Macro
+                    } else if !raw && KEYWORDS.contains(&word.as_str()) {
+                        TokenKind::Keyword
+                    } else {
+                        continue;
+                    }
+                }
+                _ => {
+                    self.at += 1;
+                    continue;
+                }
+            };
+            return Some((start..self.at, kind));
+        }
+    }
+
+    fn block_comment(&mut self, mut depth: u64) {
+        while let Some(c) = self.peek(0) {
+            if c == '/' && self.peek(1) == Some('*') {
+                depth += 1;
+                self.at += 2;
+            } else if c == '*' && self.peek(1) == Some('/') {
+                depth -= 1;
+                self.at += 2;
+                if depth == 0 {
+                    self.state = LexState::Code;
+                    return;
+                }
+            } else {
+                self.at += 1;
+            }
+        }
+        self.state = LexState::BlockComment(depth);
+    }
+
+    // NOTE: a backslash at the end of the line escapes the newline, the string goes on
+    fn string(&mut self) {
+        while let Some(c) = self.peek(0) {
+            self.at += 1;
+            match c {
+                '\\' => self.at = (self.at + 1).min(self.chars.len()),
+                '"' => {
+                    self.state = LexState::Code;
+                    return;
+                }
+                _ => {}
+            }
+        }
+        self.state = LexState::String;
+    }
+
+    fn raw_string(&mut self, hashes: u64) {
+        while let Some(c) = self.peek(0) {
+            self.at += 1;
+            if c == '"' && (0..hashes as usize).all(|offset| self.peek(offset) == Some('#')) {
+                self.at += hashes as usize;
+                self.state = LexState::Code;
+                return;
+            }
+        }
+        self.state = LexState::RawString(hashes);
+    }
+
+    // NOTE: b"", c"", br"", cr"" and r#""#, also b'' as a string token
+    fn string_prefix(&mut self) -> bool {
+        let mut offset = 0;
+        if matches!(self.peek(0), Some('b' | 'c')) {
+            offset += 1;
+        }
+        if offset == 1 && self.peek(0) == Some('b') && self.peek(1) == Some('\'') {
+            self.at += 1;
+            self.quote();
+            return true;
+        }
+        if self.peek(offset) == Some('r') {
+            offset += 1;
+            let hashes = self.chars[self.at + offset..]
+                .iter()
+                .take_while(|c| **c == '#')
+                .count();
+            if self.peek(offset + hashes) != Some('"') {
+                return false;
+            }
+            self.at += offset + hashes + 1;
+            self.raw_string(hashes as u64);
+            return true;
+        }
+        if offset == 1 && self.peek(1) == Some('"') {
+            self.at += 2;
+            self.string();
+            return true;
+        }
+        false
+    }
+
+    // NOTE: a char literal or a lifetime, a lone quote is skipped
+    fn quote(&mut self) -> Option<TokenKind> {
+        let start = self.at;
+        self.at += 1;
+        match (self.peek(0), self.peek(1)) {
+            (Some('\\'), _) => {
+                self.at += 2;
+                self.eat_while(|c| c != '\'');
+                self.at = (self.at + 1).min(self.chars.len());
+                Some(TokenKind::String)
+            }
+            (Some(_), Some('\'')) => {
+                self.at += 2;
+                Some(TokenKind::String)
+            }
+            (Some(c), _) if c == '_' || c.is_alphabetic() => {
+                self.eat_while(|c| c == '_' || c.is_alphanumeric());
+                Some(TokenKind::Lifetime)
+            }
+            _ => {
+                self.at = start + 1;
+                None
+            }
+        }
+    }
+
+    // NOTE: 1..2 and 1.max(2) are not floats, the sign after an exponent is part of the number
+    fn number(&mut self) {
+        let start = self.at;
+        let hex = self.peek(0) == Some('0') && matches!(self.peek(1), Some('x' | 'X'));
+        loop {
+            match self.peek(0) {
+                Some(c) if c == '_' || c.is_ascii_alphanumeric() => self.at += 1,
+                Some('.') if self.peek(1).is_some_and(|c| c.is_ascii_digit()) => self.at += 1,
+                Some('+' | '-')
+                    if !hex
+                        && matches!(self.chars[self.at - 1], 'e' | 'E')
+                        && self.chars[start..self.at - 1]
+                            .iter()
+                            .all(|c| c.is_ascii_digit() || matches!(c, '_' | '.')) =>
+                {
+                    self.at += 1
+                }
+                _ => break,
+            }
+        }
+    }
+}
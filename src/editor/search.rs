@@ -0,0 +1,177 @@
+use super::*;
+use regex::{Regex, RegexBuilder};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub case_insensitive: bool,
+}
+
+// NOTE: the search runs on the reassembled text so matches can span blocks and include the
+//       indentation that was stripped from their ropes
+#[derive(Debug)]
+pub struct Search {
+    regex: Option<Regex>,
+    pub(super) matches: Vec<(Point, Point)>,
+    current: Option<usize>,
+    origin: WindowState,
+}
+
+pub fn build_regex(pattern: &str, options: SearchOptions) -> std::result::Result<Regex, String> {
+    let pattern = if options.regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(options.case_insensitive)
+        .multi_line(true)
+        .build()
+        .map_err(|err| err.to_string())
+}
+
+impl Editor {
+    pub fn start_search(&mut self) {
+        self.search = Some(Search {
+            regex: None,
+            matches: Vec::new(),
+            current: None,
+            origin: self.window_state(),
+        });
+    }
+
+    pub fn update_search(
+        &mut self,
+        pattern: &str,
+        options: SearchOptions,
+    ) -> std::result::Result<(), String> {
+        let Some(search) = &mut self.search else {
+            return Ok(());
+        };
+        let origin = search.origin;
+        search.current = None;
+        search.matches.clear();
+        search.regex = None;
+        if pattern.is_empty() {
+            self.restore_window_state(&origin);
+            return Ok(());
+        }
+        let regex = build_regex(pattern, options)?;
+        let matches = self.find_matches(&regex);
+        let search = self.search.as_mut().unwrap();
+        search.matches = matches;
+        search.regex = Some(regex);
+        let index = search
+            .matches
+            .iter()
+            .position(|(start, _)| *start >= origin.cursor)
+            .or((!search.matches.is_empty()).then_some(0));
+        match index {
+            Some(index) => self.reveal_match(index),
+            None => self.restore_window_state(&origin),
+        }
+        Ok(())
+    }
+
+    pub(super) fn set_search_regex(&mut self, regex: Regex) {
+        let matches = self.find_matches(&regex);
+        if let Some(search) = &mut self.search {
+            search.matches = matches;
+            search.regex = Some(regex);
+            search.current = None;
+        }
+    }
+
+    pub(super) fn refresh_search(&mut self) {
+        let Some(regex) = self.search.as_ref().and_then(|search| search.regex.clone()) else {
+            return;
+        };
+        let matches = self.find_matches(&regex);
+        let search = self.search.as_mut().unwrap();
+        search.matches = matches;
+        search.current = None;
+    }
+
+    pub fn cancel_search(&mut self) -> bool {
+        let Some(search) = self.search.take() else {
+            return false;
+        };
+        self.restore_window_state(&search.origin);
+        true
+    }
+
+    pub fn clear_search(&mut self) -> bool {
+        self.search.take().is_some()
+    }
+
+    pub fn search_next(&mut self) -> bool {
+        self.step_search(true)
+    }
+
+    pub fn search_prev(&mut self) -> bool {
+        self.step_search(false)
+    }
+
+    pub fn search_position(&self) -> Option<(usize, usize)> {
+        let search = self.search.as_ref()?;
+        Some((search.current.map_or(0, |i| i + 1), search.matches.len()))
+    }
+
+    fn step_search(&mut self, forward: bool) -> bool {
+        let Some(search) = &self.search else {
+            return false;
+        };
+        if search.matches.is_empty() {
+            return false;
+        }
+        let cursor = self.cursor_point();
+        let len = search.matches.len();
+        let index = if forward {
+            search
+                .matches
+                .iter()
+                .position(|(start, _)| *start > cursor)
+                .unwrap_or(0)
+        } else {
+            search
+                .matches
+                .iter()
+                .rposition(|(start, _)| *start < cursor)
+                .unwrap_or(len - 1)
+        };
+        self.reveal_match(index);
+        true
+    }
+
+    fn reveal_match(&mut self, index: usize) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        search.current = Some(index);
+        let (start, _) = search.matches[index];
+        self.anchor = None;
+        // NOTE: moving out of a focused window falls back to the root window
+        self.set_cursor_point(start, self.pane_height / 2);
+    }
+
+    pub(super) fn find_matches(&self, regex: &Regex) -> Vec<(Point, Point)> {
+        let text = self.text.to_string();
+        regex
+            .find_iter(&text)
+            .filter(|m| !m.is_empty())
+            .map(|m| (self.byte_to_point(m.start()), self.byte_to_point(m.end())))
+            .collect()
+    }
+
+    fn byte_to_point(&self, byte_idx: usize) -> Point {
+        self.char_to_point(self.text.byte_to_char(byte_idx))
+    }
+
+    pub(super) fn char_to_point(&self, char_idx: usize) -> Point {
+        let line = self.text.char_to_line(char_idx);
+        Point {
+            line,
+            column: char_idx - self.text.line_to_char(line),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use super::*;
+
+// NOTE: the selection is kept as logical points over the whole text, this way it can span blocks
+//       (thus ropes) with different indentation and survives blocks being split
+
+impl Editor {
+    pub fn selection(&self) -> Option<(Point, Point)> {
+        let anchor = self.anchor?;
+        let cursor = self.cursor_point();
+        (anchor != cursor).then(|| (anchor.min(cursor), anchor.max(cursor)))
+    }
+
+    #[inline]
+    pub fn has_selection(&self) -> bool {
+        self.selection().is_some()
+    }
+
+    pub fn clear_selection(&mut self) -> bool {
+        self.mark = false;
+        self.anchor.take().is_some()
+    }
+
+    pub fn set_mark(&mut self) -> bool {
+        self.anchor = Some(self.cursor_point());
+        self.mark = true;
+        true
+    }
+
+    pub fn select(&mut self, movement: impl FnOnce(&mut Self) -> bool) -> bool {
+        if self.anchor.is_none() {
+            self.anchor = Some(self.cursor_point());
+            self.mark = false;
+        }
+        movement(self);
+        true
+    }
+
+    pub fn deselect(&mut self, movement: impl FnOnce(&mut Self) -> bool) -> bool {
+        if self.mark && self.anchor.is_some() {
+            return movement(self);
+        }
+        let cleared = self.clear_selection();
+        movement(self) || cleared
+    }
+
+    pub fn click(&mut self, x: u16, y: u16, extend: bool) -> bool {
+        if !extend {
+            self.anchor = None;
+        } else if self.anchor.is_none() {
+            self.anchor = Some(self.cursor_point());
+            self.mark = false;
+        }
+        let y = y.min(self.pane_height.saturating_sub(1));
+        let x = x.saturating_add(self.scroll_x as u16);
+        self.window.set_cursor_position(&self.vlines, x, y)
+    }
+
+    pub fn replace_selection(&mut self, text: &str) -> bool {
+        let Some((start, end)) = self.selection() else {
+            return false;
+        };
+        self.anchor = None;
+        self.replace_range(start, end, text)
+    }
+
+    pub fn insert_str(&mut self, text: &str) -> bool {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let text = self.indentation.expand(&text);
+        let (start, end) = self.selection().unwrap_or_else(|| {
+            let cursor = self.cursor_point();
+            (cursor, cursor)
+        });
+        self.anchor = None;
+        self.replace_range(start, end, &text)
+    }
+
+    pub fn indent_selection(&mut self) -> bool {
+        let width = self.indentation.width;
+        self.reindent_selection(|line| {
+            let indent = line.chars().take_while(|c| *c == ' ').count();
+            format!("{}{}", &HSPACES[..width - indent % width], line)
+        })
+    }
+
+    pub fn dedent_selection(&mut self) -> bool {
+        let width = self.indentation.width;
+        self.reindent_selection(|line| {
+            let indent = line.chars().take_while(|c| *c == ' ').count();
+            let remove = match indent % width {
+                0 => indent.min(width),
+                n => n,
+            };
+            line[remove..].to_string()
+        })
+    }
+
+    fn reindent_selection(&mut self, f: impl Fn(&str) -> String) -> bool {
+        let Some((start, end)) = self.selection() else {
+            return false;
+        };
+        let start = self.clamp_point(start);
+        let end = self.clamp_point(end);
+        let last_line = if end.column == 0 && end.line > start.line {
+            end.line - 1
+        } else {
+            end.line
+        };
+        let mut removed = String::new();
+        let mut inserted = String::new();
+        let mut deltas = Vec::new();
+        for line in start.line..=last_line {
+            let slice = self.text.line(line);
+            let old = slice.to_string();
+            let content = old.strip_suffix('\n').unwrap_or(&old);
+            let new = if is_blank(slice) {
+                content.to_string()
+            } else {
+                f(content)
+            };
+            deltas.push(new.len() as isize - content.len() as isize);
+            inserted.push_str(&new);
+            inserted.push_str(&old[content.len()..]);
+            removed.push_str(&old);
+        }
+        let shift = |point: Point| {
+            let delta = point
+                .line
+                .checked_sub(start.line)
+                .and_then(|i| deltas.get(i))
+                .copied()
+                .unwrap_or(0);
+            Point {
+                column: point.column.saturating_add_signed(delta),
+                ..point
+            }
+        };
+        let anchor = self.anchor.map(shift);
+        let cursor = shift(self.cursor_point());
+        let edit = Edit {
+            char_idx: self.text.line_to_char(start.line),
+            removed,
+            inserted,
+        };
+        if !self.apply_edit(edit, cursor) {
+            return false;
+        }
+        self.anchor = anchor;
+        true
+    }
+
+    pub(super) fn replace_range(&mut self, start: Point, end: Point, text: &str) -> bool {
+        let start = self.point_to_char(start);
+        let end = self.point_to_char(end);
+        let edit = Edit {
+            char_idx: start,
+            removed: self.text.slice(start..end).to_string(),
+            inserted: text.to_string(),
+        };
+        let mut new_text = self.text.clone();
+        edit.apply(&mut new_text);
+        let cursor_idx = start + edit.inserted.chars().count();
+        let line = new_text.char_to_line(cursor_idx);
+        let cursor = Point {
+            line,
+            column: cursor_idx - new_text.line_to_char(line),
+        };
+        self.apply_edit(edit, cursor)
+    }
+
+    pub(super) fn apply_edit(&mut self, edit: Edit, cursor: Point) -> bool {
+        if edit.removed == edit.inserted {
+            return false;
+        }
+        let state = WindowState {
+            cursor,
+            ..self.window_state()
+        };
+        let anchor = self.anchor;
+        self.record(|editor| {
+            editor.edit_text(&[edit]);
+            editor.reset_window(&state);
+            true
+        });
+        self.anchor = anchor;
+        true
+    }
+
+    pub(super) fn clamp_point(&self, point: Point) -> Point {
+        // NOTE: the text always ends with a newline so the last line given by ropey is empty
+        let last_line = self.text.len_lines().saturating_sub(2);
+        let line = point.line.min(last_line);
+        let slice = self.text.line(line);
+        let len_chars =
+            slice.len_chars() - slice.chars().last().is_some_and(|c| c == '\n') as usize;
+        let column = if point.line > last_line {
+            len_chars
+        } else {
+            point.column.min(len_chars)
+        };
+        Point { line, column }
+    }
+
+    pub(super) fn point_to_char(&self, point: Point) -> usize {
+        let Point { line, column } = self.clamp_point(point);
+        self.text.line_to_char(line) + column
+    }
+}
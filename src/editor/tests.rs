@@ -0,0 +1,353 @@
+use super::*;
+
+const SAMPLE: &str = "\
+fn main() {
+    let x = 1;
+    if x > 0 {
+        println!(\"{x}\");
+    }
+}
+
+struct A {
+    a: u32,
+}
+";
+
+fn editor(text: &str) -> Editor {
+    let mut editor = Editor::new(text);
+    editor.update_pane_size(40, 10);
+    editor
+}
+
+// NOTE: every buffer and rope is one of the chain, which holds the whole text
+fn check(editor: &Editor) {
+    let chain = editor.first_lines().map(|(key, _)| key).collect::<Vec<_>>();
+    assert_eq!(
+        chain.len(),
+        editor.buffers.len(),
+        "buffers out of the chain"
+    );
+    assert_eq!(chain.len(), editor.ropes.len(), "ropes out of the chain");
+    let mut line = 0;
+    for (key, first_line) in editor.first_lines() {
+        assert_eq!(first_line, line, "first line of a buffer");
+        line += editor.ropes[key].len_lines() - 1;
+    }
+    assert_eq!(editor.chunks().collect::<String>(), editor.text.to_string());
+}
+
+#[test]
+fn undo_in_block_keeps_buffers_in_chain() {
+    let mut editor = editor(SAMPLE);
+    editor.set_wrap(WrapMode::Pane);
+    editor.move_cursor_down();
+    assert!(editor.delete_char_forward());
+    assert!(editor.undo());
+    check(&editor);
+    assert!(editor.set_window_to_cursor());
+    assert!(editor.redo());
+    check(&editor);
+    editor.update_pane_size(30, 10);
+    check(&editor);
+    assert!(editor.undo());
+    assert_eq!(editor.text.to_string(), SAMPLE);
+    check(&editor);
+}
+
+#[test]
+fn undo_paste_in_block_then_wrap() {
+    let mut editor = editor(SAMPLE);
+    editor.move_cursor_down();
+    assert!(editor.set_window_to_cursor());
+    assert!(editor.insert_str("ab\n"));
+    assert!(editor.undo());
+    check(&editor);
+    assert!(editor.narrow_wrap());
+    check(&editor);
+    assert_eq!(editor.text.to_string(), SAMPLE);
+}
+
+#[test]
+fn undo_replace_in_block_then_wrap() {
+    let mut editor = editor(SAMPLE);
+    editor.move_cursor_down();
+    assert!(editor.set_window_to_cursor());
+    let options = SearchOptions {
+        regex: false,
+        case_insensitive: false,
+    };
+    assert_eq!(editor.replace_all("x", "xyz", options), Ok(3));
+    check(&editor);
+    assert!(editor.undo());
+    check(&editor);
+    assert!(editor.cycle_wrap());
+    check(&editor);
+    assert_eq!(editor.text.to_string(), SAMPLE);
+}
+
+#[test]
+fn replace_keeps_last_newline() {
+    let mut editor = editor("a\n    b\nc\n");
+    let options = SearchOptions {
+        regex: true,
+        case_insensitive: false,
+    };
+    assert_eq!(editor.replace_all("\\n", "", options), Ok(2));
+    assert_eq!(editor.text.to_string(), "a    bc\n");
+    check(&editor);
+}
+
+#[test]
+fn resize_after_redo_in_block() {
+    let mut editor = editor(SAMPLE);
+    assert!(editor.set_wrap(WrapMode::Pane));
+    editor.move_cursor_down();
+    assert!(editor.delete_char_forward());
+    assert!(editor.undo());
+    assert!(editor.set_window_to_cursor());
+    assert!(editor.redo());
+    editor.update_pane_size(20, 10);
+    check(&editor);
+    assert!(editor.widen_wrap());
+    check(&editor);
+}
+
+#[test]
+fn line_numbers_follow_new_lines_in_block() {
+    let mut editor = editor(SAMPLE);
+    editor.goto_line(3);
+    assert!(editor.set_window_to_cursor());
+    assert!(editor.insert_char('\n'));
+    assert!(editor.insert_str("a\nb\n"));
+    check(&editor);
+    assert!(editor.root_window());
+    assert!(editor.goto_line(10));
+    assert_eq!(editor.cursor_point().line, 10);
+    assert!(editor.delete_char_backward());
+    check(&editor);
+    assert_eq!(editor.line_count(), SAMPLE.lines().count() + 2);
+}
+
+fn written(editor: &Editor) -> String {
+    let mut file = Vec::new();
+    editor.write_to(&mut file).unwrap();
+    String::from_utf8(file).unwrap()
+}
+
+const TABS: &str = "fn a() {\n\tif b {\n\t\tc();\n\t}\n    // spaces\n}\n";
+
+#[test]
+fn tabs_keep_space_indented_lines() {
+    let mut editor = editor(TABS);
+    assert!(editor.indentation().tabs);
+    assert_eq!(written(&editor), TABS);
+    editor.move_cursor_down();
+    editor.move_cursor_down();
+    assert!(editor.insert_str("        d();\n"));
+    assert_eq!(
+        written(&editor),
+        "fn a() {\n\tif b {\n\t\td();\n\t\tc();\n\t}\n    // spaces\n}\n"
+    );
+    for _ in 0..2 {
+        editor.move_cursor_down();
+    }
+    assert!(editor.insert_str("    x"));
+    assert!(written(&editor).ends_with("\n\t}\n\tx    // spaces\n}\n"));
+}
+
+#[test]
+fn undo_indent_width() {
+    let mut editor = editor(TABS);
+    let indentation = editor.indentation();
+    assert!(editor.set_indentation(Indentation {
+        width: 8,
+        ..indentation
+    }));
+    assert!(editor.text.to_string().contains("\n                c();\n"));
+    assert_eq!(written(&editor), TABS);
+    check(&editor);
+    assert!(editor.undo());
+    assert_eq!(editor.indentation(), indentation);
+    assert_eq!(editor.text.to_string(), Editor::new(TABS).text.to_string());
+    assert_eq!(written(&editor), TABS);
+    check(&editor);
+    assert!(editor.redo());
+    assert_eq!(editor.indentation().width, 8);
+    assert_eq!(written(&editor), TABS);
+    check(&editor);
+}
+
+#[test]
+fn undo_toggle_tabs() {
+    let mut editor = editor(SAMPLE);
+    let indentation = editor.indentation();
+    assert!(editor.set_indentation(Indentation {
+        tabs: true,
+        ..indentation
+    }));
+    assert!(written(&editor).contains("\n\t\tprintln!"));
+    assert!(editor.undo());
+    assert_eq!(editor.indentation(), indentation);
+    assert_eq!(written(&editor), SAMPLE);
+}
+
+fn history_bytes(edits: Vec<Edit>) -> Vec<u8> {
+    let state = WindowState {
+        focus: None,
+        cursor: Point::default(),
+        cur_y: 0,
+    };
+    let mut history = History::default();
+    history.record_step(edits, state, state);
+    let mut bytes = Vec::new();
+    history.write_to(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn history_with_edits_out_of_the_text_is_dropped() {
+    let insert = |char_idx| Edit {
+        char_idx,
+        removed: String::new(),
+        inserted: "ab".to_string(),
+    };
+    assert!(History::read_from(&mut &history_bytes(vec![insert(3)])[..], 5).is_ok());
+    assert!(History::read_from(&mut &history_bytes(vec![insert(4)])[..], 5).is_err());
+    assert!(History::read_from(&mut &history_bytes(vec![insert(0)])[..], 1).is_err());
+    let remove = Edit {
+        char_idx: 2,
+        removed: "abc".to_string(),
+        inserted: String::new(),
+    };
+    assert!(History::read_from(&mut &history_bytes(vec![remove.clone()])[..], 2).is_ok());
+    assert!(History::read_from(&mut &history_bytes(vec![remove])[..], 1).is_err());
+}
+
+#[test]
+fn history_with_parents_out_of_the_nodes_is_dropped() {
+    let mut bytes = history_bytes(vec![Edit {
+        char_idx: 0,
+        removed: String::new(),
+        inserted: "a".to_string(),
+    }]);
+    // NOTE: the count of nodes and the current one, then the parent of the root
+    bytes[16..24].copy_from_slice(&2u64.to_le_bytes());
+    assert!(History::read_from(&mut &bytes[..], 2).is_err());
+}
+
+#[test]
+fn corrupted_history_is_not_read() {
+    let mut editor = editor(SAMPLE);
+    assert!(editor.insert_str("abc"));
+    let mut file = Vec::new();
+    editor.write_history(&mut file).unwrap();
+    let mut reopened = Editor::new(&editor.text.to_string());
+    let len = file.len();
+    // NOTE: the char index of the only edit
+    file[len - 8 * 3 - 3..len - 8 * 2 - 3].copy_from_slice(&1000u64.to_le_bytes());
+    assert!(reopened.read_history(&file[..]).is_err());
+    assert!(!reopened.undo());
+}
+
+#[test]
+fn ancestors_follow_indentation_only() {
+    let mut editor = editor("mod m {\n    fn f(\n        a: u32,\n    ) {\n        b;\n    }\n}\n");
+    for _ in 0..4 {
+        editor.move_cursor_down();
+    }
+    assert!(editor.set_window_to_cursor());
+    let headers = editor
+        .location()
+        .lines
+        .iter()
+        .map(|line| line.slice.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(headers, ["mod m {", ") {"]);
+    assert_eq!(editor.ancestor_count(), 2);
+}
+
+#[test]
+fn edit_between_apply_inverse() {
+    let old = "let é = 1;\n";
+    let new = "let éé = 12;\n";
+    let edit = Edit::between(old, new).unwrap();
+    assert_eq!(
+        edit,
+        Edit {
+            char_idx: 5,
+            removed: " = 1".to_string(),
+            inserted: "é = 12".to_string(),
+        }
+    );
+    let mut rope = Rope::from_str(old);
+    edit.apply(&mut rope);
+    assert_eq!(rope.to_string(), new);
+    edit.inverse().apply(&mut rope);
+    assert_eq!(rope.to_string(), old);
+    assert_eq!(edit.inverse().inverse(), edit);
+    assert_eq!(Edit::between(old, old), None);
+    assert_eq!(
+        Edit::diff(&Rope::from_str(old), &Rope::from_str(new)),
+        Some(edit)
+    );
+}
+
+#[test]
+fn undo_redo_restore_the_text() {
+    let mut editor = editor(SAMPLE);
+    let mut texts = vec![editor.text.to_string()];
+    editor.move_cursor_down();
+    assert!(editor.insert_str("ab"));
+    texts.push(editor.text.to_string());
+    assert!(editor.insert_char('\n'));
+    texts.push(editor.text.to_string());
+    assert!(editor.set_window_to_cursor());
+    assert!(editor.delete_char_forward());
+    texts.push(editor.text.to_string());
+    editor.move_cursor_down();
+    assert!(editor.insert_str("x\n  y\n"));
+    texts.push(editor.text.to_string());
+    assert!(texts.windows(2).all(|pair| pair[0] != pair[1]));
+    for text in texts.iter().rev().skip(1) {
+        assert!(editor.undo());
+        assert_eq!(editor.text.to_string(), *text);
+        check(&editor);
+    }
+    assert!(!editor.undo());
+    for text in &texts[1..] {
+        assert!(editor.redo());
+        assert_eq!(editor.text.to_string(), *text);
+        check(&editor);
+    }
+    assert!(!editor.redo());
+}
+
+#[test]
+fn history_round_trip() {
+    let mut editor = editor(SAMPLE);
+    editor.move_cursor_down();
+    assert!(editor.insert_str("é\n"));
+    assert!(editor.undo());
+    assert!(editor.insert_str("ab"));
+    assert!(editor.insert_char('\n'));
+    let mut file = Vec::new();
+    editor.write_history(&mut file).unwrap();
+    let text = editor.text.to_string();
+
+    let mut reopened = Editor::new(&text);
+    reopened.read_history(&file[..]).unwrap();
+    let mut again = Vec::new();
+    reopened.write_history(&mut again).unwrap();
+    assert_eq!(again, file);
+    while reopened.undo() {}
+    assert_eq!(reopened.text.to_string(), SAMPLE);
+    while reopened.redo() {}
+    assert_eq!(reopened.text.to_string(), text);
+    check(&reopened);
+    assert!(reopened.undo_earlier());
+    assert!(reopened.undo_earlier());
+    assert_eq!(
+        reopened.text.to_string(),
+        editor.text.to_string().replacen("ab\n", "é\n", 1)
+    );
+}
@@ -0,0 +1,801 @@
+use super::*;
+
+// NOTE: the normal mode reads whole commands, `[count] operator [count] motion` or
+//       `[count] command`, the keys it does not know go through the keymap as usual
+const CTRL_R: char = '\u{12}';
+// NOTE: larger counts are cut so a typo cannot keep the editor busy
+const MAX_COUNT: usize = 9999;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VimMode {
+    #[default]
+    Normal,
+    Insert,
+}
+
+impl std::fmt::Display for VimMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            VimMode::Normal => "-- NORMAL --",
+            VimMode::Insert => "-- INSERT --",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordStart,
+    WordBack,
+    WordEnd,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FirstLine,
+    LastLine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Motion(Motion),
+    // NOTE: the operator typed twice, `dd`
+    Lines,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Entry {
+    Before,
+    After,
+    LineStart,
+    LineEnd,
+    LineBelow,
+    LineAbove,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Move(Motion),
+    Operate(Operator, Target),
+    Insert(Entry),
+    Paste { before: bool },
+    Undo,
+    Redo,
+    Repeat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reach {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+#[derive(Debug)]
+enum Parsed {
+    Incomplete,
+    Invalid,
+    Command(Option<usize>, Command),
+}
+
+// NOTE: what `.` does again, the keys typed in insert mode come after the command
+#[derive(Debug, Clone)]
+struct Change {
+    count: Option<usize>,
+    command: Command,
+    typed: Vec<Keystroke>,
+}
+
+#[derive(Debug, Default)]
+pub struct Vim {
+    mode: VimMode,
+    pending: String,
+    change: Option<Change>,
+    last_change: Option<Change>,
+    // NOTE: the history steps when the current command started
+    steps: usize,
+}
+
+impl Vim {
+    pub fn mode(&self) -> VimMode {
+        self.mode
+    }
+
+    // NOTE: the keys of the command being typed
+    pub fn pending(&self) -> &str {
+        &self.pending
+    }
+
+    pub fn feed(&mut self, editor: &mut Editor, kill_ring: &mut KillRing, key: Keystroke) -> bool {
+        if self.mode == VimMode::Insert {
+            let plain = !key.ctrl && !key.alt;
+            if key.code == KeyCode::Esc && plain {
+                self.mode = VimMode::Normal;
+                self.last_change = self.change.take();
+                editor.history.squash(self.steps);
+                editor.history.seal();
+                let cursor = editor.cursor_point();
+                if cursor.column > 0 {
+                    editor.goto_point(Point {
+                        column: cursor.column - 1,
+                        ..cursor
+                    });
+                }
+                return true;
+            }
+            if let Some(change) = &mut self.change
+                && (key.typed().is_some()
+                    || plain
+                        && matches!(
+                            key.code,
+                            KeyCode::Enter | KeyCode::Backspace | KeyCode::Delete
+                        ))
+            {
+                change.typed.push(key);
+            }
+            return false;
+        }
+        let c = match key.typed() {
+            Some(c) => c,
+            None if key.ctrl && !key.alt && key.code == KeyCode::Char('r') => CTRL_R,
+            // NOTE: Esc and the other keys abandon a pending command, or do what the keymap says
+            None => return !std::mem::take(&mut self.pending).is_empty(),
+        };
+        self.pending.push(c);
+        match parse(&self.pending) {
+            Parsed::Incomplete => {}
+            Parsed::Invalid => self.pending.clear(),
+            Parsed::Command(count, command) => {
+                self.pending.clear();
+                self.steps = editor.history.step_count();
+                self.run(editor, kill_ring, count, command);
+                if self.mode == VimMode::Normal && !matches!(command, Command::Undo | Command::Redo)
+                {
+                    editor.history.squash(self.steps);
+                }
+            }
+        }
+        true
+    }
+
+    fn run(
+        &mut self,
+        editor: &mut Editor,
+        kill_ring: &mut KillRing,
+        count: Option<usize>,
+        command: Command,
+    ) {
+        let n = count.unwrap_or(1);
+        // NOTE: every command is a step of its own in the history, a whole insert is one too
+        editor.history.seal();
+        match command {
+            Command::Move(motion) => {
+                let (point, _) = editor.vim_motion(motion, count);
+                editor.goto_point(editor.vim_clamp(point));
+            }
+            Command::Operate(operator, target) => {
+                editor.vim_operate(operator, target, count, kill_ring);
+                if operator == Operator::Change {
+                    self.mode = VimMode::Insert;
+                }
+            }
+            Command::Insert(entry) => {
+                editor.vim_enter(entry);
+                self.mode = VimMode::Insert;
+            }
+            Command::Paste { before } => {
+                for _ in 0..n {
+                    editor.vim_paste(kill_ring, before);
+                }
+            }
+            Command::Undo => {
+                for _ in 0..n {
+                    if !editor.undo() {
+                        break;
+                    }
+                }
+                editor.goto_point(editor.vim_clamp(editor.cursor_point()));
+            }
+            Command::Redo => {
+                for _ in 0..n {
+                    if !editor.redo() {
+                        break;
+                    }
+                }
+                editor.goto_point(editor.vim_clamp(editor.cursor_point()));
+            }
+            Command::Repeat => {
+                if let Some(change) = self.last_change.clone() {
+                    self.repeat(editor, kill_ring, count.or(change.count), change);
+                }
+                return;
+            }
+        }
+        let change = Change {
+            count,
+            command,
+            typed: Vec::new(),
+        };
+        match command {
+            Command::Operate(Operator::Delete, _) | Command::Paste { .. } => {
+                self.last_change = Some(change)
+            }
+            Command::Operate(Operator::Change, _) | Command::Insert(_) => {
+                self.change = Some(change)
+            }
+            _ => {}
+        }
+    }
+
+    fn repeat(
+        &mut self,
+        editor: &mut Editor,
+        kill_ring: &mut KillRing,
+        count: Option<usize>,
+        change: Change,
+    ) {
+        self.run(editor, kill_ring, count, change.command);
+        if self.mode == VimMode::Insert {
+            for key in &change.typed {
+                match (key.typed(), key.code) {
+                    (Some(c), _) => editor.insert_char(c),
+                    (None, KeyCode::Enter) => editor.insert_char('\n'),
+                    (None, KeyCode::Backspace) => editor.delete_char_backward(),
+                    (None, KeyCode::Delete) => editor.delete_char_forward(),
+                    _ => false,
+                };
+            }
+            let esc = Keystroke::new(KeyCode::Esc, false, false, false);
+            self.change = None;
+            self.feed(editor, kill_ring, esc);
+            self.last_change = Some(Change { count, ..change });
+        }
+    }
+}
+
+fn parse(keys: &str) -> Parsed {
+    let mut chars = keys.chars().peekable();
+    let count = parse_count(&mut chars);
+    let Some(c) = chars.next() else {
+        return Parsed::Incomplete;
+    };
+    let operator = match c {
+        'd' => Operator::Delete,
+        'c' => Operator::Change,
+        'y' => Operator::Yank,
+        _ => {
+            let command = match c {
+                'x' => Command::Operate(Operator::Delete, Target::Motion(Motion::Right)),
+                'X' => Command::Operate(Operator::Delete, Target::Motion(Motion::Left)),
+                's' => Command::Operate(Operator::Change, Target::Motion(Motion::Right)),
+                'D' => Command::Operate(Operator::Delete, Target::Motion(Motion::LineEnd)),
+                'C' => Command::Operate(Operator::Change, Target::Motion(Motion::LineEnd)),
+                'Y' => Command::Operate(Operator::Yank, Target::Lines),
+                'i' => Command::Insert(Entry::Before),
+                'a' => Command::Insert(Entry::After),
+                'I' => Command::Insert(Entry::LineStart),
+                'A' => Command::Insert(Entry::LineEnd),
+                'o' => Command::Insert(Entry::LineBelow),
+                'O' => Command::Insert(Entry::LineAbove),
+                'p' => Command::Paste { before: false },
+                'P' => Command::Paste { before: true },
+                'u' => Command::Undo,
+                CTRL_R => Command::Redo,
+                '.' => Command::Repeat,
+                _ => match parse_motion(c, &mut chars) {
+                    Parsed::Command(_, command) => command,
+                    parsed => return parsed,
+                },
+            };
+            return Parsed::Command(count, command);
+        }
+    };
+    let inner = parse_count(&mut chars);
+    let Some(c2) = chars.next() else {
+        return Parsed::Incomplete;
+    };
+    let target = if c2 == c {
+        Target::Lines
+    } else {
+        match parse_motion(c2, &mut chars) {
+            Parsed::Command(_, Command::Move(motion)) => Target::Motion(motion),
+            parsed => return parsed,
+        }
+    };
+    let count = match (count, inner) {
+        (Some(a), Some(b)) => Some((a * b).min(MAX_COUNT)),
+        (a, b) => a.or(b),
+    };
+    Parsed::Command(count, Command::Operate(operator, target))
+}
+
+fn parse_count(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<usize> {
+    // NOTE: a lone 0 is a motion, not a count
+    if !chars.peek().is_some_and(|c| matches!(c, '1'..='9')) {
+        return None;
+    }
+    let mut count = 0;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        count = (count * 10 + digit as usize).min(MAX_COUNT);
+        chars.next();
+    }
+    Some(count)
+}
+
+fn parse_motion(c: char, chars: &mut std::iter::Peekable<std::str::Chars>) -> Parsed {
+    let motion = match c {
+        'h' => Motion::Left,
+        'l' | ' ' => Motion::Right,
+        'k' => Motion::Up,
+        'j' => Motion::Down,
+        'w' => Motion::WordStart,
+        'b' => Motion::WordBack,
+        'e' => Motion::WordEnd,
+        '0' => Motion::LineStart,
+        '^' => Motion::FirstNonBlank,
+        '$' => Motion::LineEnd,
+        'G' => Motion::LastLine,
+        'g' => match chars.next() {
+            None => return Parsed::Incomplete,
+            Some('g') => Motion::FirstLine,
+            Some(_) => return Parsed::Invalid,
+        },
+        _ => return Parsed::Invalid,
+    };
+    Parsed::Command(None, Command::Move(motion))
+}
+
+fn char_class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+impl Editor {
+    fn vim_last_line(&self) -> usize {
+        self.text.len_lines().saturating_sub(2)
+    }
+
+    fn vim_line_len(&self, line: usize) -> usize {
+        self.clamp_point(Point {
+            line,
+            column: usize::MAX,
+        })
+        .column
+    }
+
+    fn vim_first_non_blank(&self, line: usize) -> Point {
+        let column = self
+            .text
+            .line(line)
+            .chars()
+            .take_while(|c| *c == ' ')
+            .count();
+        Point {
+            line,
+            column: column.min(self.vim_line_len(line)),
+        }
+    }
+
+    fn vim_char_to_point(&self, idx: usize) -> Point {
+        let idx = idx.min(self.text.len_chars().saturating_sub(1));
+        let line = self.text.char_to_line(idx);
+        Point {
+            line,
+            column: idx - self.text.line_to_char(line),
+        }
+    }
+
+    // NOTE: the normal mode cursor sits on a char, never after the end of the line
+    fn vim_clamp(&self, point: Point) -> Point {
+        let point = self.clamp_point(point);
+        Point {
+            column: point
+                .column
+                .min(self.vim_line_len(point.line).saturating_sub(1)),
+            ..point
+        }
+    }
+
+    // NOTE: an empty line is a word of its own
+    fn vim_word_start(&self, idx: usize) -> usize {
+        let len = self.text.len_chars();
+        let mut i = idx;
+        let class = char_class(self.text.char(i));
+        if class != 0 {
+            while i < len && char_class(self.text.char(i)) == class {
+                i += 1;
+            }
+        }
+        while i < len && self.text.char(i).is_whitespace() {
+            if self.text.char(i) == '\n' && i + 1 < len && self.text.char(i + 1) == '\n' {
+                return i + 1;
+            }
+            i += 1;
+        }
+        i.min(len - 1)
+    }
+
+    fn vim_word_back(&self, idx: usize) -> usize {
+        let mut i = idx;
+        while i > 0 {
+            i -= 1;
+            let c = self.text.char(i);
+            if !c.is_whitespace() {
+                let class = char_class(c);
+                while i > 0 && char_class(self.text.char(i - 1)) == class {
+                    i -= 1;
+                }
+                return i;
+            }
+            if c == '\n' && (i == 0 || self.text.char(i - 1) == '\n') {
+                return i;
+            }
+        }
+        0
+    }
+
+    fn vim_word_end(&self, idx: usize) -> usize {
+        let len = self.text.len_chars();
+        let mut i = idx + 1;
+        while i < len && self.text.char(i).is_whitespace() {
+            i += 1;
+        }
+        if i >= len {
+            return len.saturating_sub(1);
+        }
+        let class = char_class(self.text.char(i));
+        while i + 1 < len && char_class(self.text.char(i + 1)) == class {
+            i += 1;
+        }
+        i
+    }
+
+    fn vim_motion(&self, motion: Motion, count: Option<usize>) -> (Point, Reach) {
+        let cursor = self.clamp_point(self.cursor_point());
+        let n = count.unwrap_or(1);
+        let last_line = self.vim_last_line();
+        let column = |line| cursor.column.min(self.vim_line_len(line));
+        let repeat = |f: &dyn Fn(usize) -> usize| {
+            let mut idx = self.point_to_char(cursor);
+            for _ in 0..n {
+                let next = f(idx);
+                if next == idx {
+                    break;
+                }
+                idx = next;
+            }
+            self.vim_char_to_point(idx)
+        };
+        match motion {
+            Motion::Left => (
+                Point {
+                    column: cursor.column.saturating_sub(n),
+                    ..cursor
+                },
+                Reach::Exclusive,
+            ),
+            Motion::Right => (
+                Point {
+                    column: (cursor.column + n).min(self.vim_line_len(cursor.line)),
+                    ..cursor
+                },
+                Reach::Exclusive,
+            ),
+            Motion::Up => {
+                let line = cursor.line.saturating_sub(n);
+                (
+                    Point {
+                        line,
+                        column: column(line),
+                    },
+                    Reach::Linewise,
+                )
+            }
+            Motion::Down => {
+                let line = (cursor.line + n).min(last_line);
+                (
+                    Point {
+                        line,
+                        column: column(line),
+                    },
+                    Reach::Linewise,
+                )
+            }
+            Motion::WordStart => (repeat(&|idx| self.vim_word_start(idx)), Reach::Exclusive),
+            Motion::WordBack => (repeat(&|idx| self.vim_word_back(idx)), Reach::Exclusive),
+            Motion::WordEnd => (repeat(&|idx| self.vim_word_end(idx)), Reach::Inclusive),
+            Motion::LineStart => (
+                Point {
+                    column: 0,
+                    ..cursor
+                },
+                Reach::Exclusive,
+            ),
+            Motion::FirstNonBlank => (self.vim_first_non_blank(cursor.line), Reach::Exclusive),
+            Motion::LineEnd => {
+                let line = (cursor.line + n - 1).min(last_line);
+                (
+                    Point {
+                        line,
+                        column: self.vim_line_len(line),
+                    },
+                    Reach::Exclusive,
+                )
+            }
+            Motion::FirstLine | Motion::LastLine => {
+                let line = match (motion, count) {
+                    (_, Some(n)) => n - 1,
+                    (Motion::FirstLine, None) => 0,
+                    _ => last_line,
+                };
+                (
+                    self.vim_first_non_blank(line.min(last_line)),
+                    Reach::Linewise,
+                )
+            }
+        }
+    }
+
+    fn vim_operate(
+        &mut self,
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+        kill_ring: &mut KillRing,
+    ) -> bool {
+        let n = count.unwrap_or(1);
+        let cursor = self.clamp_point(self.cursor_point());
+        let (point, reach) = match target {
+            Target::Lines => (
+                Point {
+                    line: (cursor.line + n - 1).min(self.vim_last_line()),
+                    column: cursor.column,
+                },
+                Reach::Linewise,
+            ),
+            // NOTE: like in vim, cw on a word changes up to its end and keeps the space after it
+            Target::Motion(Motion::WordStart)
+                if operator == Operator::Change
+                    && !self.text.char(self.point_to_char(cursor)).is_whitespace() =>
+            {
+                let mut idx = self.point_to_char(cursor);
+                for i in 0..n {
+                    // NOTE: the last char of a word is already its end
+                    if i > 0
+                        || char_class(self.text.char(idx + 1)) == char_class(self.text.char(idx))
+                    {
+                        idx = self.vim_word_end(idx);
+                    }
+                }
+                (self.vim_char_to_point(idx), Reach::Inclusive)
+            }
+            Target::Motion(motion) => self.vim_motion(motion, count),
+        };
+        let (start, mut end) = (cursor.min(point), cursor.max(point));
+        if reach == Reach::Linewise {
+            return self.vim_operate_lines(operator, start.line, end.line, target, kill_ring);
+        }
+        if reach == Reach::Inclusive {
+            end = self.vim_char_to_point(self.point_to_char(end) + 1);
+        } else if end.line > start.line
+            && (end.column == 0 || target == Target::Motion(Motion::WordStart))
+        {
+            // NOTE: an exclusive motion to the next line stops at the end of the line before
+            end = Point {
+                line: end.line - 1,
+                column: self.vim_line_len(end.line - 1),
+            };
+        }
+        if start == end {
+            return false;
+        }
+        kill_ring.push(self.text_between(start, end));
+        match operator {
+            Operator::Yank => self.goto_point(start),
+            Operator::Delete => {
+                self.replace_range(start, end, "");
+                self.goto_point(self.vim_clamp(start))
+            }
+            Operator::Change => self.replace_range(start, end, ""),
+        }
+    }
+
+    fn vim_operate_lines(
+        &mut self,
+        operator: Operator,
+        first: usize,
+        last: usize,
+        target: Target,
+        kill_ring: &mut KillRing,
+    ) -> bool {
+        let mut text = self.text_between(
+            Point {
+                line: first,
+                column: 0,
+            },
+            Point {
+                line: last + 1,
+                column: 0,
+            },
+        );
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        kill_ring.push(text);
+        match operator {
+            Operator::Yank => {
+                let cursor = self.cursor_point();
+                self.goto_point(Point {
+                    line: first,
+                    column: cursor.column,
+                })
+            }
+            Operator::Change => {
+                let indent = self.vim_first_non_blank(first).column;
+                let start = Point {
+                    line: first,
+                    column: indent,
+                };
+                let end = Point {
+                    line: last,
+                    column: self.vim_line_len(last),
+                };
+                self.replace_range(start, end, "") || self.goto_point(start)
+            }
+            Operator::Delete => self.vim_delete_lines(first, last, target == Target::Lines),
+        }
+    }
+
+    // NOTE: dd on the line opening an indented block dedents the block to the level of that line
+    fn vim_delete_lines(&mut self, first: usize, last: usize, dedent_block: bool) -> bool {
+        let last_line = self.vim_last_line();
+        let indent = self.vim_first_non_blank(first).column;
+        let mut end = last + 1;
+        let mut inserted = String::new();
+        if dedent_block {
+            let mut delta = None;
+            let mut block_end = end;
+            while block_end <= last_line {
+                let line = self.text.line(block_end);
+                block_end += 1;
+                if is_blank(line) {
+                    continue;
+                }
+                let line_indent = line.chars().take_while(|c| *c == ' ').count();
+                if line_indent <= indent {
+                    break;
+                }
+                delta.get_or_insert(line_indent - indent);
+                end = block_end;
+            }
+            for line in last + 1..end {
+                let line = self.text.line(line).to_string();
+                let strip = line
+                    .chars()
+                    .take_while(|c| *c == ' ')
+                    .count()
+                    .min(delta.unwrap_or(0));
+                inserted.push_str(&line[strip..]);
+            }
+        }
+        let mut start_idx = self.text.line_to_char(first);
+        let mut end_idx = self.text.line_to_char(end);
+        let mut line = first;
+        // NOTE: the text keeps its final newline, the one before the deleted lines goes instead
+        if end > last_line && inserted.is_empty() {
+            end_idx -= 1;
+            if first > 0 {
+                start_idx -= 1;
+                line -= 1;
+            }
+        }
+        let edit = Edit {
+            char_idx: start_idx,
+            removed: self.text.slice(start_idx..end_idx).to_string(),
+            inserted,
+        };
+        self.apply_edit(edit, Point { line, column: 0 })
+            && self.goto_point(self.vim_clamp(self.vim_first_non_blank(line)))
+    }
+
+    fn vim_enter(&mut self, entry: Entry) -> bool {
+        let cursor = self.clamp_point(self.cursor_point());
+        let len = self.vim_line_len(cursor.line);
+        let indent = self.vim_first_non_blank(cursor.line).column;
+        let spaces = &HSPACES[..indent.min(HSPACES.len())];
+        match entry {
+            Entry::Before => false,
+            Entry::After => self.goto_point(Point {
+                column: (cursor.column + 1).min(len),
+                ..cursor
+            }),
+            Entry::LineStart => self.goto_point(self.vim_first_non_blank(cursor.line)),
+            Entry::LineEnd => self.goto_point(Point {
+                column: len,
+                ..cursor
+            }),
+            Entry::LineBelow => {
+                let end = Point {
+                    column: len,
+                    ..cursor
+                };
+                self.replace_range(end, end, &format!("\n{spaces}"))
+            }
+            Entry::LineAbove => {
+                let start = Point {
+                    column: 0,
+                    ..cursor
+                };
+                self.replace_range(start, start, &format!("{spaces}\n"))
+                    && self.goto_point(Point {
+                        column: indent,
+                        ..cursor
+                    })
+            }
+        }
+    }
+
+    // NOTE: lines yanked whole are put on the line below or above, at the indentation of the
+    //       cursor line, anything else after or before the cursor
+    fn vim_paste(&mut self, kill_ring: &KillRing, before: bool) -> bool {
+        let Some(text) = kill_ring.get(0) else {
+            return false;
+        };
+        let cursor = self.clamp_point(self.cursor_point());
+        if !text.ends_with('\n') {
+            if !before && self.vim_line_len(cursor.line) > 0 {
+                self.goto_point(Point {
+                    column: cursor.column + 1,
+                    ..cursor
+                });
+            }
+            if !self.paste(kill_ring, 0) {
+                return false;
+            }
+            let end = self.cursor_point();
+            return self.goto_point(self.vim_clamp(Point {
+                column: end.column.saturating_sub(1),
+                ..end
+            }));
+        }
+        let indent = self.vim_first_non_blank(cursor.line).column;
+        let spaces = &HSPACES[..indent.min(HSPACES.len())];
+        let mut lines = text
+            .split_inclusive('\n')
+            .map(|line| {
+                if is_blank(RopeSlice::from(line)) {
+                    line.to_string()
+                } else {
+                    format!("{spaces}{line}")
+                }
+            })
+            .collect::<String>();
+        let line = if before { cursor.line } else { cursor.line + 1 };
+        let at = if line > self.vim_last_line() {
+            // NOTE: after the last line, the newline goes first
+            lines.pop();
+            lines.insert(0, '\n');
+            Point {
+                line: cursor.line,
+                column: self.vim_line_len(cursor.line),
+            }
+        } else {
+            Point { line, column: 0 }
+        };
+        self.replace_range(at, at, &lines) && self.goto_point(self.vim_first_non_blank(line))
+    }
+}
@@ -10,55 +10,85 @@ pub struct VLines {
     arena: SlotMap<VLineKey, VLine>,
     #[debug(skip)]
     first: VLineKey,
+    indent_width: usize,
+    word_wrap: bool,
 }
 
 impl VLines {
-    pub fn new(ropes: &RopeMap, buffer_key: BufferKey) -> Self {
+    pub fn new(
+        ropes: &RopeMap,
+        buffer_key: BufferKey,
+        wrap_at: usize,
+        indent_width: usize,
+        word_wrap: bool,
+    ) -> Self {
         let arena = SlotMap::<VLineKey, VLine>::with_key();
         let mut instance = Self {
             arena,
             first: VLineKey::null(),
+            indent_width,
+            word_wrap,
         };
-        let rope = &ropes[buffer_key];
-        let mut it = rope.lines();
-
-        let (mut prev, mut prev_end) = {
-            let line = it.next().unwrap();
-            let end_byte = line.len_bytes();
-            let key = instance.arena.insert(VLine {
-                prev: VLineKey::null(),
-                next: VLineKey::null(),
-                buffer_key,
-                start_byte: 0,
-                end_byte,
-                continuation: None,
-            });
-            instance.first = key;
-            let key = instance.wrap(ropes, key, WRAP_AT);
-            (key, end_byte)
-        };
+        instance.push_lines(ropes, buffer_key, wrap_at, VLineKey::null());
+        instance
+    }
 
-        for line in it {
+    // NOTE: the lines of the rope are linked after `prev`, the last one is returned
+    fn push_lines(
+        &mut self,
+        ropes: &RopeMap,
+        buffer_key: BufferKey,
+        wrap_at: usize,
+        mut prev: VLineKey,
+    ) -> VLineKey {
+        let mut start_byte = 0;
+        for line in ropes[buffer_key].lines() {
             let len_bytes = line.len_bytes();
             if len_bytes == 0 {
                 break;
             }
-            let end_byte = prev_end + len_bytes;
-            let key = instance.arena.insert(VLine {
-                prev: prev,
+            let key = self.arena.insert(VLine {
+                prev,
                 next: VLineKey::null(),
                 buffer_key,
-                start_byte: prev_end,
-                end_byte,
+                start_byte,
+                end_byte: start_byte + len_bytes,
                 continuation: None,
             });
-            instance.arena[prev].next = key;
-            let key = instance.wrap(ropes, key, WRAP_AT);
-            prev_end = end_byte;
-            prev = key;
+            match self.arena.get_mut(prev) {
+                Some(line) => line.next = key,
+                None => self.first = key,
+            }
+            prev = self.wrap(ropes, key, wrap_at);
+            start_byte += len_bytes;
         }
+        prev
+    }
 
-        instance
+    // NOTE: the lines from `start` up to `end` are replaced with the ones of the rope, the first
+    //       one is returned
+    pub fn replace(
+        &mut self,
+        ropes: &RopeMap,
+        start: VLineKey,
+        end: VLineKey,
+        buffer_key: BufferKey,
+        wrap_at: usize,
+    ) -> VLineKey {
+        let prev = self.arena[start].prev;
+        let mut key = start;
+        while key != end {
+            key = self.arena.remove(key).unwrap().next;
+        }
+        let last = self.push_lines(ropes, buffer_key, wrap_at, prev);
+        self.arena[last].next = end;
+        if let Some(line) = self.arena.get_mut(end) {
+            line.prev = last;
+        }
+        match self.arena.get(prev) {
+            Some(line) => line.next,
+            None => self.first,
+        }
     }
 
     fn wrap(&mut self, ropes: &RopeMap, mut key: VLineKey, wrap_at: usize) -> VLineKey {
@@ -66,14 +96,48 @@ impl VLines {
             let line = &self.arena[key];
             let slice = line.slice(ropes);
             let len_chars = slice.len_chars();
-            let indent = line.continuation.unwrap_or(0).min(wrap_at - INDENT);
-            if len_chars <= wrap_at - indent + 1 {
+            let indent = line
+                .continuation
+                .unwrap_or(0)
+                .min(wrap_at - self.indent_width);
+            // NOTE: a head line keeps its indentation and at least one more character, a block
+            //       split off later strips the indentation from it
+            let leading = if line.is_head() {
+                slice.chars().take_while(|c| *c == ' ').count() + 1
+            } else {
+                0
+            };
+            let split_idx = if self.word_wrap {
+                split_at_word(slice, wrap_at - indent, leading)
+            } else {
+                split_at_width(slice, wrap_at - indent, leading)
+            };
+            if let Some(split_idx) = split_idx {
+                let byte_idx = slice.char_to_byte(split_idx);
+                let indent = line.continuation.unwrap_or_else(|| {
+                    let indent = self.arena[key]
+                        .slice(ropes)
+                        .chars()
+                        .take_while(|c| *c == ' ')
+                        .count();
+                    indent / self.indent_width * self.indent_width
+                });
+                key = self.split_line(key, byte_idx, Some(indent));
+            } else {
                 let newline_idx = slice
                     .chars()
                     .enumerate()
                     .find_map(|(i, c)| (c == '\n').then_some(i));
                 if newline_idx == Some(len_chars.saturating_sub(1)) {
-                    break;
+                    // NOTE: a newline typed at the end of a part starts a new line with the rest
+                    let next_key = line.next;
+                    match self.arena.get_mut(next_key) {
+                        Some(next) if next.is_continuation() => {
+                            next.continuation = None;
+                            key = next_key;
+                        }
+                        _ => break,
+                    }
                 } else if let Some(newline_idx) = newline_idx {
                     let byte_idx = slice.char_to_byte(newline_idx + 1);
                     key = self.split_line(key, byte_idx, None);
@@ -86,17 +150,6 @@ impl VLines {
                 } else {
                     unreachable!("missing newline at EOF");
                 }
-            } else {
-                let byte_idx = slice.char_to_byte(wrap_at - indent);
-                let indent = line.continuation.unwrap_or_else(|| {
-                    let indent = self.arena[key]
-                        .slice(ropes)
-                        .chars()
-                        .take_while(|c| *c == ' ')
-                        .count();
-                    indent / INDENT * INDENT
-                });
-                key = self.split_line(key, byte_idx, Some(indent));
             }
         }
         key
@@ -127,10 +180,21 @@ impl VLines {
             line.end_byte += bytes;
             key = line.next;
         }
-        self.wrap(ropes, at, wrap_at)
+        self.wrap(ropes, self.wrap_start(at), wrap_at)
     }
 
-    fn remove(&mut self, ropes: &RopeMap, at: VLineKey, bytes: usize, wrap_at: usize) {
+    fn remove(
+        &mut self,
+        ropes: &RopeMap,
+        at: VLineKey,
+        start_byte: usize,
+        bytes: usize,
+        wrap_at: usize,
+    ) {
+        // NOTE: the lines a removal spanning several of them reaches are merged with the first one
+        while self.arena[at].end_byte < start_byte + bytes {
+            self.merge_next(at);
+        }
         let mut key = at;
         let buffer_key;
         {
@@ -147,7 +211,18 @@ impl VLines {
             line.end_byte -= bytes;
             key = line.next;
         }
-        self.wrap(ropes, at, wrap_at);
+        self.wrap(ropes, self.wrap_start(at), wrap_at);
+    }
+
+    // NOTE: an edit at the start of a part can join a grapheme with the end of the previous part
+    #[inline(always)]
+    fn wrap_start(&self, key: VLineKey) -> VLineKey {
+        let line = &self.arena[key];
+        if line.is_continuation() {
+            line.prev
+        } else {
+            key
+        }
     }
 
     #[inline]
@@ -195,6 +270,16 @@ impl VLines {
         self.first
     }
 
+    #[inline(always)]
+    pub fn indent_width(&self) -> usize {
+        self.indent_width
+    }
+
+    #[inline(always)]
+    pub fn set_word_wrap(&mut self, word_wrap: bool) {
+        self.word_wrap = word_wrap;
+    }
+
     #[inline]
     pub fn get(&self, key: VLineKey) -> Option<&VLine> {
         self.arena.get(key)
@@ -205,6 +290,7 @@ impl VLines {
         mut key: VLineKey,
         new_buffer_key: BufferKey,
         indent: usize,
+        old_slice: RopeSlice,
     ) -> VLineKey {
         let mut line = &mut self.arena[key];
         let old_buffer_key = line.buffer_key;
@@ -215,12 +301,12 @@ impl VLines {
             line.start_byte -= new_start_byte + cumulative_indent;
             line.end_byte -= new_start_byte + cumulative_indent;
             if line.is_head() {
-                if line.end_byte - line.start_byte > indent {
+                if !is_blank(old_slice.byte_slice(line.start_byte + cumulative_indent..)) {
                     line.end_byte -= indent;
                     cumulative_indent += indent;
                 }
             } else if let Some(continuation) = line.continuation.as_mut() {
-                *continuation -= indent;
+                *continuation = continuation.saturating_sub(indent);
             }
             let next = line.next;
             let Some(next_line) = self.arena.get_mut(next) else {
@@ -257,6 +343,12 @@ impl std::ops::Index<VLineCursor> for VLines {
     }
 }
 
+// NOTE: blank lines are kept as-is when a block is split off, they never lose or regain indentation
+#[inline]
+pub fn is_blank(slice: RopeSlice) -> bool {
+    slice.chars().take_while(|c| *c != '\n').all(|c| c == ' ')
+}
+
 #[derive(derive_more::Debug)]
 pub struct VLine {
     #[debug(skip)]
@@ -277,11 +369,11 @@ impl VLine {
     }
 
     #[inline]
-    pub fn is_indented_at(&self, ropes: &RopeMap, at: usize) -> bool {
+    pub fn is_indented_at(&self, ropes: &RopeMap, at: usize, indent_width: usize) -> bool {
         self.slice(ropes)
             .chars()
-            // NOTE: **round up** with INDENT
-            .take((at + INDENT - 1) / INDENT * INDENT)
+            // NOTE: **round up** with the indent width
+            .take(at.div_ceil(indent_width) * indent_width)
             .all(|c| c == ' ')
     }
 
@@ -360,6 +452,27 @@ impl VLineCursor {
         vlines.get(self.head_key())
     }
 
+    #[inline(always)]
+    pub fn is_continuation(&self) -> bool {
+        self.offset > 0
+    }
+
+    pub fn clamp(&self, vlines: &VLines) -> Self {
+        let offset = vlines
+            .iter(self.key)
+            .skip(1)
+            .take(self.offset)
+            .take_while(|(_, line)| line.is_continuation())
+            .count();
+        Self { offset, ..*self }
+    }
+
+    #[inline(always)]
+    pub fn offset_to(&self, other: Self) -> usize {
+        debug_assert!(self.key == other.key);
+        self.offset - other.offset
+    }
+
     #[inline(always)]
     pub fn null() -> Self {
         Self {
@@ -553,7 +666,8 @@ impl VLineCursor {
     pub fn detect_indent(&self, vlines: &VLines, ropes: &RopeMap) -> Option<usize> {
         let slice = self.full_slice(vlines, ropes);
         let indent = slice.chars().take_while(|c| *c == ' ').count();
-        (indent < slice.len_chars() - 1).then_some(indent / INDENT * INDENT)
+        let width = vlines.indent_width();
+        (indent < slice.len_chars() - 1).then_some(indent / width * width)
     }
 
     #[inline]
@@ -568,8 +682,15 @@ impl VLineCursor {
     }
 
     #[inline]
-    pub fn remove(&self, vlines: &mut VLines, ropes: &RopeMap, bytes: usize, wrap_at: usize) {
-        vlines.remove(ropes, self.key(vlines), bytes, wrap_at);
+    pub fn remove(
+        &self,
+        vlines: &mut VLines,
+        ropes: &RopeMap,
+        start_byte: usize,
+        bytes: usize,
+        wrap_at: usize,
+    ) {
+        vlines.remove(ropes, self.key(vlines), start_byte, bytes, wrap_at);
     }
 
     pub fn rewrap(&self, vlines: &mut VLines, ropes: &RopeMap, wrap_at: usize) -> Self {
@@ -584,8 +705,9 @@ impl VLineCursor {
         vlines: &mut VLines,
         new_buffer_key: BufferKey,
         indent: usize,
+        old_slice: RopeSlice,
     ) -> Self {
-        let key = vlines.update_rope(self.key, new_buffer_key, indent);
+        let key = vlines.update_rope(self.key, new_buffer_key, indent, old_slice);
         Self { key, offset: 0 }
     }
 }
@@ -608,13 +730,13 @@ impl Iterator for VLineCursorLogicalIter<'_> {
         let current_index = self.index?;
         self.index = if self.reversed {
             if current_index != self.start_bound {
-                self.index?.peek_prev_logical(&self.vlines)
+                self.index?.peek_prev_logical(self.vlines)
             } else {
                 None
             }
         } else {
             self.index?
-                .peek_next_logical(&self.vlines)
+                .peek_next_logical(self.vlines)
                 .filter(|idx| *idx != self.end_bound)
         };
         Some(current_index)
@@ -640,16 +762,13 @@ impl VLineCursorLogicalIter<'_> {
     pub fn find_block_edge(&mut self, ropes: &RopeMap, indent: usize) -> Option<VLineCursor> {
         let vlines = &self.vlines;
         let indent = &HSPACES[..indent];
-        self.find_map(|cur| {
+        self.find(|cur| {
             let slice = cur.full_slice(vlines, ropes);
             let slice = slice.slice(..(slice.len_chars() - 1));
             if slice.len_chars() == 0 || slice.chars().all(|c| c == ' ') {
-                return None;
-            }
-            if slice.len_chars() < indent.len() || slice.slice(..indent.len()) != indent {
-                return Some(cur);
+                return false;
             }
-            None
+            slice.len_chars() < indent.len() || slice.slice(..indent.len()) != indent
         })
     }
 }
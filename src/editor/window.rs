@@ -24,12 +24,14 @@ struct Position {
     newlines: usize,
     relative_x: usize,
     invalid: bool,
+    // NOTE: where the cursor is drawn, a column inside a wide grapheme is drawn at its start
+    x: usize,
     #[debug(skip)]
     cursor: VLineCursor,
 }
 
 impl Window {
-    pub fn new(buffers: &BufferMap, vlines: &VLines, start: VLineCursor, end: VLineCursor) -> Self {
+    pub fn new(start: VLineCursor, end: VLineCursor, indent: usize) -> Self {
         Self {
             start,
             scroll: start,
@@ -39,19 +41,56 @@ impl Window {
             position: None,
             cur_y: 0,
             cur_x: 0,
-            indent: buffers[vlines[start].buffer_key].indent,
+            indent,
             prepend_newlines: 0,
         }
     }
 
+    pub fn clamp_indent(&mut self, indent: usize) {
+        if indent < self.indent {
+            self.indent = indent;
+            self.clear_position();
+        }
+    }
+
+    // NOTE: a rewrapped line can have less parts than the one the view was scrolled to
+    pub fn clamp_scroll(&mut self, vlines: &VLines) {
+        let scroll = self.scroll.clamp(vlines);
+        self.start_idx -= self.scroll.offset_to(scroll);
+        self.scroll = scroll;
+        self.clear_position();
+    }
+
     #[inline]
-    pub fn get_display_lines<'r>(
+    pub fn get_display_lines<'v, 'r>(
         &self,
-        vlines: &VLines,
+        vlines: &'v VLines,
         ropes: &'r RopeMap,
-        buffers: &BufferMap,
-    ) -> impl Iterator<Item = DisplayLine<'r>> {
+        buffers: &'r BufferMap,
+        line: usize,
+        selection: Option<(Point, Point)>,
+        matches: &'r [(Point, Point)],
+    ) -> DisplayLineIter<'v, 'r, 'r> {
         debug_assert!(!self.scroll.is_null());
+        // NOTE: the first line may be a continuation, its column is after the previous parts
+        let head = VLineCursor::new(vlines, self.scroll.head_key());
+        let column = if self.scroll == head {
+            0
+        } else {
+            let head_line = &vlines[head];
+            let rest = ropes[head_line.buffer_key].byte_slice(head_line.start_byte..);
+            let indent = if is_blank(rest) {
+                0
+            } else {
+                buffers[head_line.buffer_key].indent
+            };
+            vlines
+                .iter(head.head_key())
+                .take_while(|(key, _)| *key != self.scroll.key(vlines))
+                .map(|(_, line)| line.slice(ropes).len_chars())
+                .sum::<usize>()
+                + indent
+        };
         DisplayLineIter {
             ropes,
             buffers,
@@ -60,6 +99,13 @@ impl Window {
             dedent: self.indent,
             prepend_newlines: self.prepend_newlines,
             empty_slice: vlines[self.scroll].slice(ropes).slice(0..0),
+            selection,
+            matches,
+            tokens: &[],
+            cursors: &[],
+            line,
+            column,
+            started: false,
         }
     }
 
@@ -68,6 +114,21 @@ impl Window {
         self.start
     }
 
+    #[inline(always)]
+    pub fn scroll(&self) -> VLineCursor {
+        self.scroll
+    }
+
+    #[inline(always)]
+    pub fn end(&self) -> VLineCursor {
+        self.end
+    }
+
+    #[inline(always)]
+    pub fn indent(&self) -> usize {
+        self.indent
+    }
+
     #[inline]
     pub fn cursor(&self, vlines: &VLines) -> VLineCursor {
         let mut cursor = self.scroll;
@@ -82,6 +143,51 @@ impl Window {
         cursor
     }
 
+    pub fn set_cursor(&mut self, vlines: &VLines, target: VLineCursor, x: u16, y: u16) -> bool {
+        let mut cursor = self.start;
+        let mut idx = 0;
+        while cursor != target {
+            if !cursor.move_next_visual_if(vlines, |cur| cur != self.end) {
+                return false;
+            }
+            idx += 1;
+        }
+        let cur_y = idx.min(y as usize);
+        let mut scroll = target;
+        for _ in 0..cur_y {
+            scroll.move_prev_visual(vlines);
+        }
+        self.scroll = scroll;
+        self.start_idx = idx - cur_y;
+        self.cursor_idx = cur_y;
+        self.cur_y = cur_y as u16;
+        self.cur_x = x;
+        self.prepend_newlines = 0;
+        self.clear_position();
+        true
+    }
+
+    pub fn set_cursor_position(&mut self, vlines: &VLines, x: u16, y: u16) -> bool {
+        // NOTE: the blank lines shown above the start of the window go away
+        let mut y = y.saturating_sub(self.prepend_newlines as u16);
+        self.prepend_newlines = 0;
+        let mut cursor = self.scroll;
+        let mut visible = 0;
+        while visible < y && cursor.move_next_visual_if(vlines, |cur| cur != self.end) {
+            visible += 1;
+        }
+        if visible < y && self.end.is_null() {
+            // NOTE: past the end of the text, the cursor is on a virtual line
+            visible = y;
+        }
+        y = visible;
+        self.cursor_idx = y as usize;
+        self.cur_y = y;
+        self.cur_x = x;
+        self.clear_position();
+        true
+    }
+
     pub fn scroll_up(&mut self, vlines: &VLines) -> bool {
         if self.start_idx > 0 && self.scroll.move_prev_visual(vlines) {
             self.start_idx -= 1;
@@ -128,16 +234,8 @@ impl Window {
         self.position.unwrap()
     }
 
-    #[inline]
-    fn with_position(&mut self, f: impl Fn(&mut Position)) {
-        if let Some(mut position) = self.position.take() {
-            f(&mut position);
-            self.position = Some(position);
-        }
-    }
-
     #[inline(always)]
-    fn clear_position(&mut self) {
+    pub fn clear_position(&mut self) {
         self.position = None;
     }
 
@@ -159,24 +257,24 @@ impl Window {
         });
         let rope = &ropes[line.buffer_key];
         let mut char_idx = rope.byte_to_char(line.start_byte);
+        let mut x = self.cur_x as usize;
         let trailing_spaces: usize;
-        let slice = line.slice(ropes);
-        let mut len_chars = slice.len_chars();
-        if slice.chars_at(len_chars).reversed().next().unwrap() == '\n' {
-            len_chars -= 1;
-        }
         if self.prepend_newlines > 0 {
-            trailing_spaces = relative_x as usize;
+            trailing_spaces = relative_x;
             newlines = 0;
         } else if newlines > 0 {
             char_idx = rope.byte_to_char(line.end_byte);
-            trailing_spaces = relative_x as usize;
-        } else if len_chars >= relative_x as usize {
-            char_idx += relative_x as usize;
-            trailing_spaces = 0;
+            trailing_spaces = relative_x;
         } else {
-            char_idx += len_chars;
-            trailing_spaces = relative_x - len_chars;
+            // NOTE: the continuation of a line is drawn after its own indentation
+            let continuation = line.continuation.unwrap_or(0);
+            let column = relative_x.saturating_sub(continuation);
+            let (chars, width, trailing) = column_to_char(line.slice(ropes), column);
+            char_idx += chars;
+            trailing_spaces = trailing;
+            if !invalid {
+                x = indent.saturating_sub(self.indent) + continuation + width + trailing;
+            }
         }
         Position {
             trailing_spaces,
@@ -184,14 +282,104 @@ impl Window {
             newlines,
             relative_x,
             invalid,
+            x,
             cursor,
         }
     }
 
+    pub fn cursor_offset(
+        &self,
+        vlines: &VLines,
+        ropes: &RopeMap,
+        buffers: &BufferMap,
+    ) -> (VLineCursor, usize, usize) {
+        let Position {
+            char_idx,
+            trailing_spaces,
+            newlines,
+            invalid,
+            cursor,
+            ..
+        } = self.get_position(vlines, ropes, buffers);
+        let head = VLineCursor::new(vlines, cursor.head_key());
+        if invalid || newlines > 0 || self.prepend_newlines > 0 {
+            return (head, self.cur_x as usize + self.indent, newlines);
+        }
+        let line = &vlines[head];
+        let start_char = ropes[line.buffer_key].byte_to_char(line.start_byte);
+        let column = buffers[line.buffer_key].indent + char_idx - start_char + trailing_spaces;
+        (head, column, 0)
+    }
+
     pub fn cursor_position<T: From<u16>>(&self) -> (T, T) {
         (T::from(self.cur_x), T::from(self.cur_y))
     }
 
+    pub fn cursor_x(&self, vlines: &VLines, ropes: &RopeMap, buffers: &BufferMap) -> u16 {
+        match self.position {
+            Some(position) => position.x as u16,
+            None => self.get_position(vlines, ropes, buffers).x as u16,
+        }
+    }
+
+    // NOTE: the x of the start of the text of a visual line
+    fn line_x(&self, line: &VLine, buffers: &BufferMap) -> usize {
+        buffers[line.buffer_key].indent.saturating_sub(self.indent) + line.continuation.unwrap_or(0)
+    }
+
+    // NOTE: an edit rewraps from the previous part of the line, which can take some of the text
+    //       back, the cursor follows the char from there
+    fn move_cursor_to_wrap_start(&mut self, vlines: &VLines, cursor: VLineCursor) -> VLineCursor {
+        let Some(prev) = cursor
+            .peek_prev_visual(vlines)
+            .filter(|_| cursor.is_continuation())
+        else {
+            return cursor;
+        };
+        if self.cur_y > 0 {
+            self.cur_y -= 1;
+            self.cursor_idx -= 1;
+        } else {
+            self.scroll_up(vlines);
+        }
+        prev
+    }
+
+    // NOTE: an edited line is rewrapped, the char may have moved to one of the next visual lines
+    fn move_cursor_to_char(
+        &mut self,
+        vlines: &VLines,
+        ropes: &RopeMap,
+        buffers: &BufferMap,
+        mut cursor: VLineCursor,
+        char_idx: usize,
+        limit: u16,
+    ) {
+        loop {
+            let line = &vlines[cursor];
+            let rope = &ropes[line.buffer_key];
+            let end = rope.byte_to_char(line.end_byte);
+            if char_idx < end || (char_idx == end && rope.char(end - 1) != '\n') {
+                break;
+            }
+            let Some(next) = cursor.peek_next_visual(vlines) else {
+                break;
+            };
+            cursor = next;
+            if self.cur_y < limit {
+                self.cur_y += 1;
+                self.cursor_idx += 1;
+            } else {
+                self.scroll_down(vlines);
+            }
+        }
+        let line = &vlines[cursor];
+        let start = ropes[line.buffer_key].byte_to_char(line.start_byte);
+        let column = char_to_column(line.slice(ropes), char_idx - start);
+        self.cur_x = (self.line_x(line, buffers) + column) as u16;
+        self.clear_position();
+    }
+
     pub fn move_cursor_up(&mut self, vlines: &VLines) -> bool {
         if self.cur_y > 0 {
             self.cur_y -= 1;
@@ -208,6 +396,7 @@ impl Window {
     pub fn move_cursor_down(&mut self, vlines: &VLines, limit: u16) -> bool {
         if self.prepend_newlines > 0 {
             self.prepend_newlines -= 1;
+            self.clear_position();
         } else if self.cur_y < limit {
             self.cur_y += 1;
             self.cursor_idx += 1;
@@ -224,7 +413,28 @@ impl Window {
         ropes: &RopeMap,
         buffers: &BufferMap,
     ) -> bool {
-        if self.cur_x > 0 {
+        let Position {
+            char_idx,
+            trailing_spaces,
+            newlines,
+            invalid,
+            x,
+            cursor,
+            ..
+        } = self.position(vlines, ropes, buffers);
+        let line = &vlines[cursor];
+        let start = ropes[line.buffer_key].byte_to_char(line.start_byte);
+        let on_text =
+            !invalid && newlines == 0 && self.prepend_newlines == 0 && trailing_spaces == 0;
+        let column = if on_text {
+            char_to_column(line.slice(ropes), char_idx - start)
+        } else {
+            0
+        };
+        if column > 0 {
+            let (_, prev, _) = column_to_char(line.slice(ropes), column - 1);
+            self.cur_x = (x - (column - prev)) as u16;
+        } else if self.cur_x > 0 && !(on_text && line.is_continuation()) {
             // TODO not if continuation line
             self.cur_x -= 1;
         } else {
@@ -237,7 +447,17 @@ impl Window {
                 // TODO seems inconsistent with move_cursor_right
                 return false;
             }
-            self.move_cursor_at_end(vlines, ropes, buffers);
+            if on_text && line.is_continuation() {
+                // NOTE: the end of the previous part is the same char, go to its last grapheme
+                let mut prev = cursor;
+                prev.move_prev_visual(vlines);
+                let line = &vlines[prev];
+                let width = text_width(line.slice(ropes));
+                let (_, column, _) = column_to_char(line.slice(ropes), width.saturating_sub(1));
+                self.cur_x = (self.line_x(line, buffers) + column) as u16;
+            } else {
+                self.move_cursor_at_end(vlines, ropes, buffers);
+            }
         }
         self.clear_position();
         true
@@ -259,12 +479,44 @@ impl Window {
         vlines: &VLines,
         ropes: &RopeMap,
         buffers: &BufferMap,
+        width: usize,
     ) -> bool {
-        if self.cur_x as usize + 1 < WRAP_AT {
-            self.cur_x += 1;
+        let Position {
+            char_idx,
+            trailing_spaces,
+            newlines,
+            invalid,
+            x,
+            cursor,
+            ..
+        } = self.position(vlines, ropes, buffers);
+        let mut next_x = self.cur_x as usize + 1;
+        let mut in_text = false;
+        if !invalid && newlines == 0 && self.prepend_newlines == 0 && trailing_spaces == 0 {
+            let line = &vlines[cursor];
+            let slice = line.slice(ropes);
+            let start = ropes[line.buffer_key].byte_to_char(line.start_byte);
+            let column = char_to_column(slice, char_idx - start);
+            next_x = x + next_column(slice, column).map_or(1, |next| next - column);
+            // NOTE: the end of a wrapped part is the same char as the start of the next part
+            if next_x - x + column >= text_width(slice)
+                && vlines.get(line.next).is_some_and(VLine::is_continuation)
+            {
+                next_x = width;
+            } else {
+                // NOTE: a line not wrapped can be wider than the width
+                in_text = next_x - x + column <= text_width(slice);
+            }
+        }
+        if next_x < width || in_text {
+            self.cur_x = next_x as u16;
         } else {
-            self.cur_y += 1;
-            self.cursor_idx += 1;
+            if self.prepend_newlines > 0 {
+                self.prepend_newlines -= 1;
+            } else {
+                self.cur_y += 1;
+                self.cursor_idx += 1;
+            }
             self.move_cursor_at_start(vlines, ropes, buffers);
         }
         self.clear_position();
@@ -272,8 +524,8 @@ impl Window {
     }
 
     #[allow(dead_code)]
-    pub fn move_cursor_right_saturating(&mut self) -> bool {
-        if self.cur_x as usize + 1 < WRAP_AT {
+    pub fn move_cursor_right_saturating(&mut self, width: usize) -> bool {
+        if self.cur_x as usize + 1 < width {
             self.cur_x += 1;
             self.clear_position();
             true
@@ -305,15 +557,13 @@ impl Window {
             } = self.position(vlines, ropes, buffers);
             if newlines == 0 {
                 let line = &vlines[cursor];
-                self.cur_x = line
-                    .slice(ropes)
+                let slice = line.slice(ropes);
+                let char_idx = slice
                     .chars()
                     .enumerate()
-                    .find_map(|(i, c)| (!c.is_whitespace()).then_some(i as u16))
+                    .find_map(|(i, c)| (!c.is_whitespace()).then_some(i))
                     .unwrap_or(0);
-                let buffer = &buffers[vlines[cursor].buffer_key];
-                self.cur_x += buffer.indent.saturating_sub(self.indent) as u16
-                    + line.continuation.unwrap_or(0) as u16;
+                self.cur_x = (self.line_x(line, buffers) + char_to_column(slice, char_idx)) as u16;
             } else {
                 self.cur_x = 0;
             }
@@ -340,15 +590,13 @@ impl Window {
                 let line = &vlines[cursor];
                 let slice = line.slice(ropes);
                 let len_chars = slice.len_chars();
-                self.cur_x = slice
+                let char_idx = slice
                     .chars_at(len_chars)
                     .reversed()
                     .enumerate()
-                    .find_map(|(i, c)| (!c.is_whitespace()).then_some((len_chars - i) as u16))
+                    .find_map(|(i, c)| (!c.is_whitespace()).then_some(len_chars - i))
                     .unwrap_or(0);
-                let buffer = &buffers[vlines[cursor].buffer_key];
-                self.cur_x += buffer.indent.saturating_sub(self.indent) as u16
-                    + line.continuation.unwrap_or(0) as u16;
+                self.cur_x = (self.line_x(line, buffers) + char_to_column(slice, char_idx)) as u16;
             } else {
                 self.cur_x = 0;
             }
@@ -365,20 +613,22 @@ impl Window {
         vlines: &mut VLines,
         ropes: &mut RopeMap,
         buffers: &BufferMap,
+        edits: &mut Vec<BufferEdit>,
+        limit: u16,
     ) -> bool {
         let Position {
             trailing_spaces,
             mut char_idx,
             relative_x,
             invalid,
-            cursor,
+            mut cursor,
             ..
         } = self.position(vlines, ropes, buffers);
         let line = &vlines[cursor];
         let buffer = &buffers[line.buffer_key];
-        if invalid || line.is_indented_at(ropes, relative_x) {
+        if invalid || line.is_indented_at(ropes, relative_x, vlines.indent_width()) {
             return false;
-        } else if char_idx >= ropes[line.buffer_key].len_chars() {
+        } else if char_idx + 1 >= ropes[line.buffer_key].len_chars() {
             return true;
         }
         if self.prepend_newlines > 0 {
@@ -386,6 +636,7 @@ impl Window {
             buffer.insert(
                 vlines,
                 ropes,
+                edits,
                 char_idx,
                 &VSPACES[..self.prepend_newlines],
                 cursor,
@@ -393,14 +644,25 @@ impl Window {
             self.prepend_newlines = 0;
         }
         if trailing_spaces > 0 {
-            buffer.insert(vlines, ropes, char_idx, &HSPACES[..trailing_spaces], cursor);
+            buffer.insert(
+                vlines,
+                ropes,
+                edits,
+                char_idx,
+                &" ".repeat(trailing_spaces),
+                cursor,
+            );
             char_idx += trailing_spaces;
         }
-        buffer.remove(vlines, ropes, char_idx, cursor);
-        self.with_position(|p| {
-            p.char_idx = char_idx;
-            p.trailing_spaces = 0;
-        });
+        let target = vline_at_char(vlines, ropes, cursor, char_idx);
+        let line = &vlines[target];
+        let start = ropes[line.buffer_key].byte_to_char(line.start_byte);
+        let end = start + next_grapheme_boundary(line.slice(ropes), char_idx - start);
+        if target == cursor {
+            cursor = self.move_cursor_to_wrap_start(vlines, cursor);
+        }
+        buffer.remove(vlines, ropes, edits, char_idx..end, target);
+        self.move_cursor_to_char(vlines, ropes, buffers, cursor, char_idx, limit);
         true
     }
 
@@ -409,6 +671,7 @@ impl Window {
         vlines: &mut VLines,
         ropes: &mut RopeMap,
         buffers: &BufferMap,
+        edits: &mut Vec<BufferEdit>,
         c: char,
         limit: u16,
     ) -> bool {
@@ -416,13 +679,13 @@ impl Window {
             trailing_spaces,
             mut char_idx,
             newlines,
-            mut relative_x,
+            relative_x,
             invalid,
             mut cursor,
             ..
         } = self.position(vlines, ropes, buffers);
         let line = &vlines[cursor];
-        if invalid || (c == ' ' && line.is_indented_at(ropes, relative_x)) {
+        if invalid || (c == ' ' && line.is_indented_at(ropes, relative_x, vlines.indent_width())) {
             self.clear_position();
             return false;
         }
@@ -431,13 +694,14 @@ impl Window {
             buffer.insert(
                 vlines,
                 ropes,
+                edits,
                 char_idx,
                 &VSPACES[..self.prepend_newlines],
                 cursor,
             );
             self.prepend_newlines = 0;
         } else if newlines > 0 {
-            let key = buffer.insert(vlines, ropes, char_idx, &VSPACES[..newlines], cursor);
+            let key = buffer.insert(vlines, ropes, edits, char_idx, &VSPACES[..newlines], cursor);
             cursor = VLineCursor::new(vlines, key);
             // TODO why does it work without it
             self.end = cursor
@@ -446,42 +710,22 @@ impl Window {
             char_idx += newlines - 1;
         }
         if trailing_spaces > 0 {
-            buffer.insert(vlines, ropes, char_idx, &HSPACES[..trailing_spaces], cursor);
+            buffer.insert(
+                vlines,
+                ropes,
+                edits,
+                char_idx,
+                &" ".repeat(trailing_spaces),
+                cursor,
+            );
             char_idx += trailing_spaces;
         }
-        buffer.insert_char(vlines, ropes, char_idx, c, cursor);
-        char_idx += 1;
-        if c == '\n' {
-            if self.cur_y < limit {
-                self.cursor_idx += 1;
-                cursor.move_next_visual(vlines);
-                self.cur_y += 1;
-            } else {
-                self.scroll_down(vlines);
-            }
-            self.cur_x = (buffer.indent - self.indent) as _;
-            relative_x = 0;
-        } else if relative_x + 1 > buffer.wrap_at {
-            if self.cur_y < limit {
-                self.cursor_idx += 1;
-                cursor.move_next_visual(vlines);
-                self.cur_y += 1;
-            } else {
-                self.scroll_down(vlines);
-            }
-            self.cur_x = (buffer.indent + 1 - self.indent) as _;
-            relative_x = 1;
-        } else {
-            self.cur_x += 1;
-            relative_x += 1;
-        }
-        self.with_position(|p| {
-            p.char_idx = char_idx;
-            p.trailing_spaces = 0;
-            p.newlines = 0;
-            p.relative_x = relative_x;
-            p.cursor = cursor;
-        });
+        let target = vline_at_char(vlines, ropes, cursor, char_idx);
+        buffer.insert_char(vlines, ropes, edits, char_idx, c, target);
+        if target == cursor {
+            cursor = self.move_cursor_to_wrap_start(vlines, cursor);
+        }
+        self.move_cursor_to_char(vlines, ropes, buffers, cursor, char_idx + 1, limit);
         true
     }
 
@@ -490,12 +734,13 @@ impl Window {
         vlines: &mut VLines,
         ropes: &mut RopeMap,
         buffers: &BufferMap,
+        edits: &mut Vec<BufferEdit>,
+        limit: u16,
     ) -> bool {
         let Position {
-            mut char_idx,
+            char_idx,
             trailing_spaces,
             newlines,
-            relative_x,
             invalid,
             mut cursor,
             ..
@@ -507,49 +752,57 @@ impl Window {
             return false;
         } else if char_idx == 0 {
             return true;
-        } else if relative_x > 0 {
+        } else if trailing_spaces > 0 {
             self.cur_x -= 1;
-            self.with_position(|p| p.relative_x -= 1);
-        } else if self.cur_y > 0 {
+            self.clear_position();
+            return true;
+        } else if newlines > 0 {
             self.cur_y -= 1;
             self.cursor_idx -= 1;
-            char_idx -= 1;
-            if newlines == 0 {
-                cursor.move_prev_visual(vlines);
-                let len_chars = vlines[cursor].slice(ropes).len_chars().saturating_sub(1);
-                self.cur_x = (len_chars + buffer.indent - self.indent) as u16;
-                buffer.remove(vlines, ropes, char_idx, cursor);
-                self.with_position(|p| {
-                    p.char_idx = char_idx;
-                    p.relative_x = len_chars;
-                    p.cursor = cursor;
-                });
-            } else if newlines == 1 {
-                let len_chars = vlines[cursor].slice(ropes).len_chars().saturating_sub(1);
-                self.cur_x = (len_chars + buffer.indent - self.indent) as u16;
-                self.with_position(|p| {
-                    p.char_idx = char_idx;
-                    p.relative_x = len_chars;
-                    p.newlines = 0;
-                });
-            } else {
-                self.with_position(|p| p.newlines -= 1);
+            if newlines == 1 {
+                self.cur_x = (self.line_x(line, buffers) + text_width(line.slice(ropes))) as u16;
             }
+            self.clear_position();
+            return true;
+        } else if self.prepend_newlines > 0 {
             return true;
-        } else {
-            if self.scroll_up(vlines) {
-                let line = &vlines[cursor];
-                let len_chars = line.slice(ropes).len_chars().saturating_sub(1);
-                self.cur_x = (len_chars + buffer.indent - self.indent) as u16;
-            }
         }
-        if trailing_spaces == 0 {
-            char_idx -= 1;
-            buffer.remove(vlines, ropes, char_idx, cursor);
-            self.with_position(|p| p.char_idx = char_idx);
-        } else {
-            self.with_position(|p| p.trailing_spaces -= 1);
+        let start = ropes[line.buffer_key].byte_to_char(line.start_byte);
+        if char_idx == start {
+            // NOTE: the grapheme before the cursor is at the end of the previous visual line
+            cursor.move_prev_visual(vlines);
+            if self.cur_y > 0 {
+                self.cur_y -= 1;
+                self.cursor_idx -= 1;
+            } else {
+                self.scroll_up(vlines);
+            }
         }
+        let line = &vlines[cursor];
+        let start = ropes[line.buffer_key].byte_to_char(line.start_byte);
+        let from = start + prev_grapheme_boundary(line.slice(ropes), char_idx - start);
+        buffer.remove(vlines, ropes, edits, from..char_idx, cursor);
+        let cursor = self.move_cursor_to_wrap_start(vlines, cursor);
+        self.move_cursor_to_char(vlines, ropes, buffers, cursor, from, limit);
         true
     }
 }
+
+// NOTE: the visual line of a char that may have been pushed further by an edit of the line
+pub fn vline_at_char(
+    vlines: &VLines,
+    ropes: &RopeMap,
+    mut cursor: VLineCursor,
+    char_idx: usize,
+) -> VLineCursor {
+    loop {
+        let line = &vlines[cursor];
+        if char_idx < ropes[line.buffer_key].byte_to_char(line.end_byte) {
+            return cursor;
+        }
+        let Some(next) = cursor.peek_next_visual(vlines) else {
+            return cursor;
+        };
+        cursor = next;
+    }
+}
@@ -0,0 +1,158 @@
+use super::*;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    At(usize),
+    Pane,
+    // NOTE: lines are scrolled horizontally instead
+    Off,
+}
+
+impl Default for WrapMode {
+    fn default() -> Self {
+        Self::At(WRAP_AT)
+    }
+}
+
+impl std::fmt::Display for WrapMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::At(width) => write!(f, "Wrap at {width}"),
+            Self::Pane => write!(f, "Wrap at the pane width"),
+            Self::Off => write!(f, "No wrap"),
+        }
+    }
+}
+
+impl Editor {
+    #[inline(always)]
+    pub fn wrap(&self) -> WrapMode {
+        self.wrap
+    }
+
+    pub fn set_wrap(&mut self, wrap: WrapMode) -> bool {
+        let changed = self.wrap != wrap;
+        self.wrap = wrap;
+        self.rewrap() || changed
+    }
+
+    pub fn cycle_wrap(&mut self) -> bool {
+        self.set_wrap(match self.wrap {
+            WrapMode::At(_) => WrapMode::Pane,
+            WrapMode::Pane => WrapMode::Off,
+            WrapMode::Off => WrapMode::default(),
+        })
+    }
+
+    pub fn widen_wrap(&mut self) -> bool {
+        self.set_wrap(WrapMode::At(self.wrap_at.min(MAX_WRAP_AT) + INDENT))
+    }
+
+    pub fn narrow_wrap(&mut self) -> bool {
+        self.set_wrap(WrapMode::At(
+            self.wrap_at.min(MAX_WRAP_AT).saturating_sub(INDENT),
+        ))
+    }
+
+    #[inline(always)]
+    pub fn word_wrap(&self) -> bool {
+        self.word_wrap
+    }
+
+    pub fn set_word_wrap(&mut self, word_wrap: bool) -> bool {
+        if self.word_wrap == word_wrap {
+            return false;
+        }
+        self.word_wrap = word_wrap;
+        self.vlines.set_word_wrap(word_wrap);
+        self.relayout(self.wrap_at);
+        true
+    }
+
+    fn wrap_width(&self) -> usize {
+        match self.wrap {
+            WrapMode::At(width) => width,
+            WrapMode::Pane if self.pane_width > 0 => self.pane_width as usize,
+            WrapMode::Pane => WRAP_AT,
+            WrapMode::Off => return NO_WRAP,
+        }
+        .clamp(MIN_WRAP_AT, MAX_WRAP_AT)
+    }
+
+    // NOTE: the blocks are kept and their starts are line heads which survive the rewrap, only
+    //       the position of the cursor in the window has to be found again
+    pub(super) fn rewrap(&mut self) -> bool {
+        let width = self.wrap_width();
+        if width == self.wrap_at {
+            return false;
+        }
+        self.relayout(width);
+        true
+    }
+
+    fn relayout(&mut self, width: usize) {
+        let cursor = self.cursor_point();
+        let (_, cur_y) = self.window.cursor_position::<u16>();
+        let (start, end, indent) = (self.window.start(), self.window.end(), self.window.indent());
+        self.wrap_at = width;
+        // NOTE: only the buffers of the chain have lines
+        let keys = self.first_lines().map(|(key, _)| key).collect::<Vec<_>>();
+        for key in keys {
+            self.buffers[key].set_width(&mut self.vlines, &self.ropes, width);
+        }
+        self.window = Window::new(start, end, indent);
+        self.set_cursor_point(cursor, cur_y);
+        self.scroll_x = 0;
+        self.follow_cursor_x();
+    }
+
+    pub(super) fn follow_cursor_x(&mut self) {
+        let width = self.pane_width as usize;
+        if width == 0 {
+            return;
+        }
+        let x = self
+            .window
+            .cursor_x(&self.vlines, &self.ropes, &self.buffers) as usize;
+        if x < self.scroll_x {
+            self.scroll_x = x;
+        } else if x >= self.scroll_x + width {
+            self.scroll_x = x + 1 - width;
+        }
+    }
+}
+
+impl DisplayLine<'_> {
+    pub(super) fn scrolled(mut self, x: usize) -> Self {
+        if x == 0 {
+            return self;
+        }
+        let from_indent = x.min(self.indent.len());
+        self.indent = &self.indent[from_indent..];
+        let columns = x - from_indent;
+        let (mut skip, width, trailing) = column_to_char(self.slice, columns);
+        if trailing == 0 && width < columns {
+            // NOTE: a wide grapheme cut by the edge of the pane is replaced with spaces
+            let next = next_grapheme_boundary(self.slice, skip);
+            let next_width = char_to_column(self.slice, next);
+            self.indent = &HSPACES[..next_width - columns];
+            skip = next;
+        }
+        let skip = skip.min(self.slice.len_chars());
+        self.slice = self.slice.slice(skip..);
+        let shift = |range: Range<usize>| {
+            let range = range.start.saturating_sub(skip)..range.end.saturating_sub(skip);
+            (range.start < range.end).then_some(range)
+        };
+        self.selected = self.selected.and_then(shift);
+        self.highlights = self.highlights.into_iter().filter_map(shift).collect();
+        self.tokens = self
+            .tokens
+            .into_iter()
+            .filter_map(|(range, kind)| Some((shift(range)?, kind)))
+            .collect();
+        self.cursors = self.cursors.into_iter().filter_map(shift).collect();
+        self
+    }
+}
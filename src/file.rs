@@ -0,0 +1,129 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::editor::*;
+
+#[derive(Debug, Default)]
+pub struct OpenFile {
+    path: Option<PathBuf>,
+    history_path: Option<PathBuf>,
+}
+
+impl OpenFile {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            history_path: None,
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn load_history(&mut self, editor: &mut Editor) {
+        let Some(history_path) = self.path().and_then(|path| history_path(path, editor)) else {
+            return;
+        };
+        // NOTE: a missing or unreadable history is not worth bothering the user: start afresh
+        if let Ok(file) = File::open(&history_path)
+            && editor.read_history(BufReader::new(file)).is_ok()
+        {
+            self.history_path = Some(history_path);
+        }
+    }
+
+    pub fn save(&mut self, editor: &Editor) -> io::Result<()> {
+        let Some(path) = self.path() else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no file name"));
+        };
+        write_atomic(path, |writer| editor.write_to(writer))
+    }
+
+    pub fn save_history(&mut self, editor: &Editor) -> io::Result<()> {
+        let Some(history_path) = self.path().and_then(|path| history_path(path, editor)) else {
+            return Ok(());
+        };
+        if let Some(dir) = history_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(&history_path, |writer| editor.write_history(writer))?;
+        if let Some(old) = self.history_path.replace(history_path)
+            && Some(&old) != self.history_path.as_ref()
+        {
+            let _ = fs::remove_file(old);
+        }
+        Ok(())
+    }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".config")))?;
+    Some(dir.join("jaffacode"))
+}
+
+// NOTE: `JAFFACODE_KEYS` is the path of the key bindings, they are added to the default ones
+pub fn load_keymap(keymap: &mut Keymap) -> Result<(), String> {
+    let Some(path) = std::env::var_os("JAFFACODE_KEYS")
+        .map(PathBuf::from)
+        .or_else(|| Some(config_dir()?.join("keys.toml")))
+    else {
+        return Ok(());
+    };
+    match fs::read_to_string(&path) {
+        Ok(text) => keymap
+            .load(&text)
+            .map_err(|err| format!("{}: {err}", path.display())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(format!("{}: {err}", path.display())),
+    }
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".cache")))?;
+    Some(dir.join("jaffacode").join("undo"))
+}
+
+// NOTE: the history is only valid for the exact text it was saved with, so the content hash is
+//       part of the name: a file changed behind our back simply has no history
+fn history_path(path: &Path, editor: &Editor) -> Option<PathBuf> {
+    let path = fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .ok()?;
+    let path_hash = fnv1a([path.as_os_str().as_encoded_bytes()]);
+    Some(cache_dir()?.join(format!("{:016x}-{:016x}", path_hash, editor.content_hash())))
+}
+
+fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    // NOTE: follow symlinks so that renaming replaces the target, not the link
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(path.file_name().unwrap_or_default());
+    tmp_name.push(".jaffacode~");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let res = (|| {
+        let file = File::create(&tmp_path)?;
+        if let Ok(metadata) = fs::metadata(&path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&tmp_path, &path)
+    })();
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res
+}
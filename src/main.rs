@@ -1,8 +1,13 @@
+mod cli;
+mod clipboard;
 mod editor;
+mod file;
+mod theme;
 
 use crossterm::{
     event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
     },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
@@ -10,27 +15,82 @@ use crossterm::{
 use ratatui::prelude::*;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Offset},
-    widgets::{Block, Borders, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
 };
 use std::io;
+use std::ops::Range;
 use std::time::Duration;
 
 use editor::*;
+use file::*;
+use theme::*;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let files = match cli::parse(std::env::args_os().skip(1)) {
+        Ok(cli::Command::Open(files)) => files,
+        Ok(cli::Command::Help) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Err(err) => {
+            eprintln!("jaffacode: {err}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let mut open_files = Vec::new();
+    let mut editors = Vec::new();
+    let mut start_lines = Vec::new();
+    for file in &files {
+        let text = file.read().unwrap_or_else(|err| {
+            eprintln!("jaffacode: {err}");
+            std::process::exit(1);
+        });
+        let mut editor = Editor::new(&text);
+        let mut open_file = OpenFile::new(file.path());
+        open_file.load_history(&mut editor);
+        editor.set_highlighter(open_file.path().and_then(highlighter_for));
+        open_files.push(open_file);
+        editors.push(editor);
+        start_lines.push(file.line);
+    }
+    if editors.is_empty() {
+        open_files.push(OpenFile::default());
+        editors.push(Editor::new(""));
+        start_lines.push(None);
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = execute!(
+            io::stdout(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            DisableBracketedPaste
+        );
+        default_hook(info);
+    }));
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let res = run_app(&mut terminal);
+    let res = run_app(&mut terminal, open_files, editors, start_lines);
 
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
 
@@ -41,20 +101,57 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
-    let mut editors = vec![
-        Editor::new(&std::fs::read_to_string(
-            option_env!("FILE").unwrap_or("src/editor.rs"),
-        )?),
-        Editor::new(include_str!("editor/window.rs")),
-    ];
-    let constraints = std::iter::repeat_n(Constraint::Fill(1), editors.len()).collect::<Vec<_>>();
+fn run_app<B: Backend + io::Write>(
+    terminal: &mut Terminal<B>,
+    mut open_files: Vec<OpenFile>,
+    mut editors: Vec<Editor>,
+    // NOTE: the line given for each file is only gone to once its pane has a size to center it in
+    mut start_lines: Vec<Option<usize>>,
+) -> io::Result<()> {
+    let mut status = String::new();
+    let mut prompt: Option<Prompt> = None;
+    let mut kill_ring = KillRing::default();
+    let mut clipboard = clipboard::from_env();
+    let mut picker: Option<usize> = None;
+    // NOTE: the row of the header picked with the keyboard, the outermost block is the first
+    let mut breadcrumb: Option<usize> = None;
     let mut active_editor = 0;
     let mut scroll: usize = 1;
     let mut debug = false;
+    let mut relative_numbers = false;
+    let mut vim: Option<Vim> = None;
+    // NOTE: kills in a row make a single entry of the kill ring, any other event ends the row
+    let mut last_action: Option<Action> = None;
     let mut terminal_size = None;
+    let mut pane_areas = vec![Rect::default(); editors.len()];
+    // NOTE: the line of the text shown on each row of the location header above a pane
+    let mut headers = vec![(Rect::default(), Vec::new()); editors.len()];
+    let mut keymap = Keymap::default();
+    if let Err(err) = load_keymap(&mut keymap) {
+        status = err;
+    }
+    let (mut theme, mut theme_file) = theme::from_env();
+    if let Some(file) = &mut theme_file {
+        match file.load() {
+            Ok(loaded) => theme = loaded,
+            Err(err) => status = err,
+        }
+    }
 
     loop {
+        if let Some(file) = &mut theme_file
+            && file.changed()
+        {
+            match file.load() {
+                Ok(loaded) => {
+                    theme = loaded;
+                    status = "Theme reloaded".to_string();
+                }
+                Err(err) => status = err,
+            }
+            terminal_size = None;
+        }
+
         let draw = {
             let new_size = terminal.size().ok();
             if terminal_size == new_size {
@@ -66,52 +163,58 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
         };
 
         if draw {
+            // NOTE: the editors follow the size of their panes before the drawing, which only
+            //       reads them
+            let size = terminal.size()?;
+            let areas = screen_areas(Rect::new(0, 0, size.width, size.height), &editors, debug);
+            for (i, editor) in editors.iter_mut().enumerate() {
+                let (header, pane) = areas.editors[i];
+                let gutter = gutter_width(editor);
+                editor.update_pane_size(pane.width.saturating_sub(gutter), pane.height);
+                if let Some(line) = start_lines[i].take() {
+                    editor.goto_line(line);
+                }
+                pane_areas[i] = pane;
+                headers[i] = (
+                    header,
+                    editor
+                        .location()
+                        .lines
+                        .iter()
+                        .map(|line| line.line_number)
+                        .collect(),
+                );
+            }
             terminal.draw(|f| {
-                let cols = Layout::default()
-                    .direction(Direction::Horizontal)
-                    .constraints([Constraint::Min(43), Constraint::Fill(2)])
-                    .split(f.area());
-                let editor_areas = if debug {
-                    Layout::default()
-                        .direction(Direction::Vertical)
-                        .constraints(&constraints)
-                        .split(cols[0])
-                } else {
-                    Layout::default()
-                        .direction(Direction::Horizontal)
-                        .constraints(&constraints)
-                        .split(f.area())
-                };
-
-                for (i, editor) in editors.iter_mut().enumerate() {
+                for (i, editor) in editors.iter().enumerate() {
                     let location = editor.location();
-                    let editor_comps = Layout::default()
-                        .direction(Direction::Vertical)
-                        .constraints([
-                            Constraint::Length(location.lines.len() as _),
-                            Constraint::Percentage(100),
-                        ])
-                        .split(editor_areas[i]);
-                    let block = Block::default().fg(Color::White).bg(Color::Blue);
+                    let (header, pane) = areas.editors[i];
+                    let block = Block::default().style(theme.header);
                     let p = Paragraph::new(
                         location
                             .lines
                             .into_iter()
-                            .map(|DisplayLine { slice, indent, .. }| {
-                                Line::from(vec![Span::raw(indent), Span::raw(slice)])
+                            .enumerate()
+                            .map(|(row, DisplayLine { slice, indent, .. })| {
+                                let line = Line::from(vec![Span::raw(indent), Span::raw(slice)]);
+                                if i == active_editor && breadcrumb == Some(row) {
+                                    line.style(theme.selection)
+                                } else {
+                                    line
+                                }
                             })
                             .collect::<Vec<_>>(),
                     )
                     .block(block);
-                    f.render_widget(p, editor_comps[0]);
-                    let mut block = Block::default().fg(Color::Gray);
-                    let inner = block.inner(editor_comps[1]);
-                    editor.update_pane_size(inner.width, inner.height);
+                    f.render_widget(p, header);
+                    let mut block = Block::default().style(theme.inactive);
+                    let gutter = gutter_width(editor);
+                    let cursor_line = editor.cursor_point().line;
                     if i == active_editor {
-                        block = block.fg(Color::White);
+                        block = Block::default().style(theme.active);
                         let (mut x, y) = editor.cursor_position();
-                        x += 4;
-                        let offset = inner.offset(Offset { x, y }).intersection(inner);
+                        x += gutter as i32;
+                        let offset = pane.offset(Offset { x, y }).intersection(pane);
                         if !offset.is_empty() {
                             f.set_cursor_position(offset);
                         }
@@ -123,23 +226,121 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
                                 |DisplayLine {
                                      slice,
                                      indent,
-                                     continuation,
+                                     line_number,
+                                     selected,
+                                     highlights,
+                                     tokens,
+                                     cursors,
                                  }| {
-                                    let mut info = Span::raw(format!("{:02}  ", indent.len()));
-                                    if continuation {
-                                        info = info.fg(Color::Green);
-                                    } else if indent.len() > 0 {
-                                        info = info.fg(Color::Blue);
-                                    } else {
-                                        info = info.fg(Color::Gray);
-                                    }
-                                    Line::from(vec![info, Span::raw(indent), Span::raw(slice)])
+                                    let number = match line_number {
+                                        Some(line) if relative_numbers && line != cursor_line => {
+                                            line.abs_diff(cursor_line).to_string()
+                                        }
+                                        Some(line) => (line + 1).to_string(),
+                                        None => String::new(),
+                                    };
+                                    let width = gutter as usize - 1;
+                                    let info = Span::raw(format!("{number:>width$} ")).style(
+                                        match line_number {
+                                            Some(line) if line == cursor_line => theme.line_number,
+                                            Some(_) => theme.gutter,
+                                            None => theme.continuation,
+                                        },
+                                    );
+                                    let mut spans = vec![info, Span::raw(indent)];
+                                    spans.extend(text_spans(
+                                        slice,
+                                        selected,
+                                        &highlights,
+                                        &tokens,
+                                        &cursors,
+                                        &theme,
+                                    ));
+                                    Line::from(spans)
                                 },
                             )
                             .collect::<Vec<_>>(),
                     )
                     .block(block);
-                    f.render_widget(p, editor_comps[1]);
+                    f.render_widget(p, pane);
+                }
+
+                if let Some(selected) = picker {
+                    let items = kill_ring
+                        .iter()
+                        .map(|entry| {
+                            let mut lines = entry.lines();
+                            let first = lines.next().unwrap_or_default();
+                            match lines.count() {
+                                0 => ListItem::new(first.to_string()),
+                                n => ListItem::new(format!("{first} (+{n} lines)")),
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    let [_, area, _] = Layout::vertical([
+                        Constraint::Fill(1),
+                        Constraint::Length(items.len().min(10) as u16 + 2),
+                        Constraint::Fill(1),
+                    ])
+                    .areas(areas.main);
+                    let [_, area, _] = Layout::horizontal([
+                        Constraint::Percentage(20),
+                        Constraint::Percentage(60),
+                        Constraint::Percentage(20),
+                    ])
+                    .areas(area);
+                    let list = List::new(items)
+                        .block(Block::default().borders(Borders::ALL).title("Kill ring"))
+                        .highlight_style(theme.selection);
+                    f.render_widget(Clear, area);
+                    f.render_stateful_widget(
+                        list,
+                        area,
+                        &mut ListState::default().with_selected(Some(selected)),
+                    );
+                }
+
+                if let Some(prompt) = &prompt {
+                    let text = match (&prompt.kind, editors[active_editor].replace_preview()) {
+                        (PromptKind::ReplaceConfirm, Some((matched, with))) => {
+                            format!("Replace {matched:?} with {with:?}? (y/n/a/q) ")
+                        }
+                        _ => format!("{}{}", prompt.kind.label(), prompt.input),
+                    };
+                    let info = match (&prompt.error, &prompt.kind) {
+                        (Some(err), _) => format!("  ({err})"),
+                        (None, PromptKind::Search(_) | PromptKind::Replace(_))
+                            if !prompt.input.is_empty() =>
+                        {
+                            match editors[active_editor].search_position() {
+                                Some((i, n)) => format!("  [{i}/{n}]"),
+                                None => String::new(),
+                            }
+                        }
+                        _ => String::new(),
+                    };
+                    f.set_cursor_position(areas.status.offset(Offset {
+                        x: text.chars().count() as _,
+                        y: 0,
+                    }));
+                    f.render_widget(
+                        Paragraph::new(Line::from(vec![
+                            Span::raw(text),
+                            Span::raw(info).style(theme.hint),
+                        ])),
+                        areas.status,
+                    );
+                } else if let Some(vim) = &vim {
+                    f.render_widget(
+                        Paragraph::new(Line::from(vec![
+                            Span::raw(format!("{} ", vim.mode())).style(theme.hint),
+                            Span::raw(format!("{} ", vim.pending())),
+                            Span::raw(status.as_str()),
+                        ])),
+                        areas.status,
+                    );
+                } else {
+                    f.render_widget(Paragraph::new(status.as_str()), areas.status);
                 }
 
                 if debug {
@@ -152,73 +353,520 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
                     )
                     .wrap(Wrap { trim: false })
                     .block(Block::default().borders(Borders::ALL));
-                    f.render_widget(p, cols[1]);
+                    f.render_widget(p, areas.debug);
                 }
             })?;
         }
 
         if event::poll(Duration::from_millis(100))? {
             let mut event = event::read()?;
+            // NOTE: the pointer moving over the panes does nothing, it keeps the last action
+            let previous_action = match event {
+                Event::Mouse(MouseEvent {
+                    kind: MouseEventKind::Moved,
+                    ..
+                }) => last_action,
+                _ => last_action.take(),
+            };
+
+            // NOTE: the other keys bound to DeleteBackward, Ctrl-H by default, are a Backspace for
+            //       the prompts and the vim layer too
+            if let Event::Key(ref mut key) = event
+                && key.code != KeyCode::Backspace
+                && !keymap.is_pending()
+                && Keystroke::try_from(*key)
+                    .is_ok_and(|key| keymap.lookup(&[key]) == Some(Action::DeleteBackward))
+            {
+                key.code = KeyCode::Backspace;
+                key.modifiers = KeyModifiers::NONE;
+            }
+
+            if let Some(current) = &mut prompt {
+                let Event::Key(key) = event else {
+                    continue;
+                };
+                terminal_size = None;
+                let editor = &mut editors[active_editor];
+                if let PromptKind::ReplaceConfirm = current.kind {
+                    let done = match key.code {
+                        KeyCode::Char('y') | KeyCode::Char(' ') => !editor.replace_current(),
+                        KeyCode::Char('n') | KeyCode::Backspace => !editor.skip_current(),
+                        KeyCode::Char('a') | KeyCode::Char('!') => {
+                            editor.replace_remaining();
+                            true
+                        }
+                        KeyCode::Char('q') | KeyCode::Esc | KeyCode::Enter => true,
+                        _ => false,
+                    };
+                    if done {
+                        let count = editor.finish_replace();
+                        status = format!("Replaced {count} occurrence(s)");
+                        prompt = None;
+                    }
+                    continue;
+                }
+                let mut changed = false;
+                match key.code {
+                    KeyCode::Esc => {
+                        if !matches!(current.kind, PromptKind::Travel | PromptKind::GotoLine) {
+                            editor.cancel_search();
+                        }
+                        prompt = None;
+                    }
+                    KeyCode::Backspace => {
+                        current.input.pop();
+                        changed = true;
+                    }
+                    KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::ALT) => {
+                        if let PromptKind::Search(options) | PromptKind::Replace(options) =
+                            &mut current.kind
+                        {
+                            match c {
+                                'r' => options.regex ^= true,
+                                'c' => options.case_insensitive ^= true,
+                                _ => {}
+                            }
+                            changed = true;
+                        }
+                    }
+                    KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        editor.search_next();
+                    }
+                    KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        if let PromptKind::ReplaceWith(options, pattern) = &current.kind {
+                            editor.cancel_search();
+                            status = match editor.replace_all(pattern, &current.input, *options) {
+                                Ok(0) => format!("Not found: {pattern}"),
+                                Ok(count) => format!("Replaced {count} occurrence(s)"),
+                                Err(err) => err,
+                            };
+                            prompt = None;
+                        }
+                    }
+                    KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        current.input.push(c);
+                        changed = true;
+                    }
+                    KeyCode::Down => {
+                        editor.search_next();
+                    }
+                    KeyCode::Up => {
+                        editor.search_prev();
+                    }
+                    KeyCode::Enter => {
+                        let Prompt { kind, input, .. } = prompt.take().unwrap();
+                        status = match kind {
+                            PromptKind::Travel => match parse_duration(&input) {
+                                Some(secs) if editor.undo_travel(secs) => String::new(),
+                                Some(_) => "Already there".to_string(),
+                                None => format!("Invalid duration: {input}"),
+                            },
+                            PromptKind::GotoLine => {
+                                let current = editor.cursor_point().line;
+                                match parse_line(&input, current) {
+                                    Some(line) => {
+                                        editor.goto_line(line.min(editor.line_count() - 1));
+                                        String::new()
+                                    }
+                                    None => format!("Invalid line number: {input}"),
+                                }
+                            }
+                            PromptKind::Search(_) => match editor.search_position() {
+                                Some((_, 0)) | None => format!("Not found: {input}"),
+                                Some((_, n)) => {
+                                    let keys = |action| {
+                                        keymap
+                                            .keys_for(action)
+                                            .map_or(action.to_string(), |keys| format_keys(&keys))
+                                    };
+                                    format!(
+                                        "{n} matches, {}/{} to cycle",
+                                        keys(Action::SearchNext),
+                                        keys(Action::SearchPrev)
+                                    )
+                                }
+                            },
+                            PromptKind::Replace(options) => {
+                                prompt = Some(Prompt {
+                                    kind: PromptKind::ReplaceWith(options, input),
+                                    input: String::new(),
+                                    error: None,
+                                });
+                                String::new()
+                            }
+                            PromptKind::ReplaceWith(options, pattern) => {
+                                editor.cancel_search();
+                                match editor.start_replace(&pattern, &input, options) {
+                                    Ok(true) => {
+                                        prompt = Some(Prompt {
+                                            kind: PromptKind::ReplaceConfirm,
+                                            input: String::new(),
+                                            error: None,
+                                        });
+                                        String::new()
+                                    }
+                                    Ok(false) => format!("Not found: {pattern}"),
+                                    Err(err) => err,
+                                }
+                            }
+                            PromptKind::ReplaceConfirm => unreachable!(),
+                        };
+                    }
+                    _ => {}
+                }
+                if changed
+                    && let Some(current) = &mut prompt
+                    && let PromptKind::Search(options) | PromptKind::Replace(options) = current.kind
+                {
+                    current.error = editor.update_search(&current.input, options).err();
+                }
+                continue;
+            }
+
+            if let Some(selected) = &mut picker {
+                let Event::Key(key) = event else {
+                    continue;
+                };
+                terminal_size = None;
+                match key.code {
+                    KeyCode::Esc => picker = None,
+                    KeyCode::Up => *selected = selected.saturating_sub(1),
+                    KeyCode::Down => *selected = (*selected + 1).min(kill_ring.len() - 1),
+                    KeyCode::Enter => {
+                        kill_ring.promote(*selected);
+                        editors[active_editor].paste(&kill_ring, 0);
+                        picker = None;
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            if let Some(selected) = &mut breadcrumb {
+                let Event::Key(key) = event else {
+                    continue;
+                };
+                terminal_size = None;
+                let count = editors[active_editor].ancestor_count();
+                match key.code {
+                    KeyCode::Esc => breadcrumb = None,
+                    KeyCode::Up => *selected = selected.saturating_sub(1),
+                    KeyCode::Down => *selected = (*selected + 1).min(count.saturating_sub(1)),
+                    KeyCode::Enter => {
+                        editors[active_editor].set_window_to_ancestor(count - *selected);
+                        breadcrumb = None;
+                    }
+                    _ => {}
+                }
+                continue;
+            }
 
-            match event {
-                Event::Key(
-                    ref mut key @ KeyEvent {
-                        code: KeyCode::Char('h'),
-                        modifiers: KeyModifiers::CONTROL,
+            let chord = keymap.is_pending();
+            // NOTE: the keys no binding can name go to the editor like the other events
+            let keystroke = match &event {
+                Event::Key(key) => Keystroke::try_from(*key).ok(),
+                _ => None,
+            };
+            let action = match (event, keystroke) {
+                // NOTE: the vim layer sees the keys first, except in the middle of a chord
+                (Event::Key(_), Some(key))
+                    if !chord
+                        && vim.as_mut().is_some_and(|vim| {
+                            vim.feed(&mut editors[active_editor], &mut kill_ring, key)
+                        }) =>
+                {
+                    terminal_size = None;
+                    status.clear();
+                    continue;
+                }
+                (Event::Key(_), Some(key)) => match keymap.feed(key) {
+                    Resolved::Action(action) => {
+                        if chord {
+                            status.clear();
+                        }
+                        action
+                    }
+                    Resolved::Pending(keys) => {
+                        terminal_size = None;
+                        status = format!("{}-", format_keys(&keys));
+                        continue;
+                    }
+                    Resolved::Unbound(keys) => {
+                        terminal_size = None;
+                        match keys[..] {
+                            [key] if key.typed().is_some() => {
+                                status.clear();
+                                editors[active_editor].insert_char(key.typed().unwrap());
+                            }
+                            [_] => {}
+                            _ => status = format!("{} is not bound", format_keys(&keys)),
+                        }
+                        continue;
+                    }
+                },
+                (
+                    Event::Mouse(MouseEvent {
+                        kind:
+                            kind @ (MouseEventKind::Down(MouseButton::Left)
+                            | MouseEventKind::Drag(MouseButton::Left)),
+                        column,
+                        row,
+                        ..
+                    }),
+                    _,
+                ) => {
+                    let drag = matches!(kind, MouseEventKind::Drag(_));
+                    let position = Position { x: column, y: row };
+                    // NOTE: a row of the header focuses the block around the line it shows
+                    if !drag
+                        && let Some(i) =
+                            headers.iter().position(|(area, _)| area.contains(position))
+                    {
+                        active_editor = i;
+                        let (area, lines) = &headers[i];
+                        let row = (row - area.y) as usize;
+                        if let Some(line) = lines[..=row.min(lines.len() - 1)]
+                            .iter()
+                            .rev()
+                            .find_map(|line| *line)
+                        {
+                            editors[i].set_window_to_line(line);
+                        }
+                        terminal_size = None;
+                        continue;
+                    }
+                    if !drag
+                        && let Some(i) = pane_areas.iter().position(|area| area.contains(position))
+                    {
+                        active_editor = i;
+                    }
+                    let area = pane_areas[active_editor];
+                    // NOTE: the first columns of a pane show the line numbers
+                    let gutter = gutter_width(&editors[active_editor]);
+                    let x = column.saturating_sub(area.x + gutter);
+                    let y = row.saturating_sub(area.y);
+                    if editors[active_editor].click(x, y, drag) {
+                        terminal_size = None;
+                    }
+                    continue;
+                }
+                // NOTE: the wheel scrolls the pane under the pointer, which does not take the focus
+                (
+                    Event::Mouse(MouseEvent {
+                        kind: kind @ (MouseEventKind::ScrollUp | MouseEventKind::ScrollDown),
+                        column,
+                        row,
                         ..
-                    },
+                    }),
+                    _,
                 ) => {
-                    key.code = KeyCode::Backspace;
-                    key.modifiers = KeyModifiers::NONE;
+                    let position = Position { x: column, y: row };
+                    let i = pane_areas
+                        .iter()
+                        .zip(&headers)
+                        .position(|(pane, (header, _))| {
+                            pane.contains(position) || header.contains(position)
+                        })
+                        .unwrap_or(active_editor);
+                    let scrolled = match kind {
+                        MouseEventKind::ScrollUp => editors[i].scroll_up(),
+                        _ => editors[i].scroll_down(),
+                    };
+                    if scrolled {
+                        terminal_size = None;
+                    }
+                    continue;
                 }
-                _ => {}
-            }
+                (event, _) => {
+                    if editors[active_editor].handle_event(event) {
+                        terminal_size = None;
+                    }
+                    continue;
+                }
+            };
+            last_action = Some(action);
 
-            match event {
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('w'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                })
-                | Event::Key(KeyEvent {
-                    code: KeyCode::Tab, ..
-                }) => {
+            match action {
+                Action::NextPane => {
                     active_editor += 1;
                     active_editor %= editors.len();
                     terminal_size = None;
-                    continue;
                 }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('q'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }) => return Ok(()),
-                Event::Key(KeyEvent {
-                    code: KeyCode::PageUp,
-                    modifiers: KeyModifiers::ALT,
-                    ..
-                }) => {
+                // NOTE: Tab switches panes when there is nothing to indent
+                Action::IndentSelectionOrNextPane => {
+                    if editors[active_editor].has_selection() {
+                        editors[active_editor].indent_selection();
+                    } else {
+                        active_editor += 1;
+                        active_editor %= editors.len();
+                    }
+                    terminal_size = None;
+                }
+                Action::Quit => return Ok(()),
+                Action::Save => {
+                    terminal_size = None;
+                    let open_file = &mut open_files[active_editor];
+                    let editor = &editors[active_editor];
+                    status = match open_file.path().map(|path| path.display().to_string()) {
+                        Some(path) => match open_file.save(editor) {
+                            Ok(()) => match open_file.save_history(editor) {
+                                Ok(()) => format!("Saved {path}"),
+                                Err(err) => {
+                                    format!("Saved {path} but could not save its history: {err}")
+                                }
+                            },
+                            Err(err) => format!("Could not save {path}: {err}"),
+                        },
+                        None => "No file name".to_string(),
+                    };
+                }
+                Action::Copy => {
+                    if editors[active_editor].copy(&mut kill_ring) {
+                        terminal_size = None;
+                        status = copy_to_clipboard(&mut clipboard, &kill_ring, terminal);
+                    }
+                }
+                Action::Cut => {
+                    if editors[active_editor].cut(&mut kill_ring) {
+                        terminal_size = None;
+                        status = copy_to_clipboard(&mut clipboard, &kill_ring, terminal);
+                    }
+                }
+                Action::KillLine => {
+                    if editors[active_editor]
+                        .kill_line(&mut kill_ring, previous_action == Some(Action::KillLine))
+                    {
+                        terminal_size = None;
+                        status = copy_to_clipboard(&mut clipboard, &kill_ring, terminal);
+                    }
+                }
+                Action::Paste => {
+                    if editors[active_editor].paste(&kill_ring, 0) {
+                        terminal_size = None;
+                    }
+                }
+                Action::PasteNext => {
+                    if editors[active_editor].paste_next(&kill_ring) {
+                        terminal_size = None;
+                    }
+                }
+                Action::CycleWrap | Action::WidenWrap | Action::NarrowWrap => {
+                    terminal_size = None;
+                    let editor = &mut editors[active_editor];
+                    match action {
+                        Action::CycleWrap => editor.cycle_wrap(),
+                        Action::WidenWrap => editor.widen_wrap(),
+                        _ => editor.narrow_wrap(),
+                    };
+                    status = editor.wrap().to_string();
+                }
+                Action::ToggleWordWrap => {
+                    terminal_size = None;
+                    let editor = &mut editors[active_editor];
+                    editor.set_word_wrap(!editor.word_wrap());
+                    status = if editor.word_wrap() {
+                        "Wrap at word boundaries"
+                    } else {
+                        "Wrap anywhere"
+                    }
+                    .to_string();
+                }
+                Action::NextIndentWidth | Action::ToggleTabs => {
+                    terminal_size = None;
+                    let editor = &mut editors[active_editor];
+                    let indentation = editor.indentation();
+                    editor.set_indentation(if action == Action::NextIndentWidth {
+                        indentation.next_width()
+                    } else {
+                        Indentation {
+                            tabs: !indentation.tabs,
+                            ..indentation
+                        }
+                    });
+                    status = editor.indentation().to_string();
+                }
+                Action::KillRing => {
+                    terminal_size = None;
+                    if kill_ring.is_empty() {
+                        status = "Kill ring is empty".to_string();
+                    } else {
+                        picker = Some(0);
+                    }
+                }
+                Action::PickAncestor => {
+                    terminal_size = None;
+                    match editors[active_editor].ancestor_count() {
+                        0 => status = "No enclosing block".to_string(),
+                        count => breadcrumb = Some(count - 1),
+                    }
+                }
+                Action::UndoTravel => {
+                    terminal_size = None;
+                    prompt = Some(Prompt {
+                        kind: PromptKind::Travel,
+                        input: String::new(),
+                        error: None,
+                    });
+                }
+                Action::GotoLine => {
+                    terminal_size = None;
+                    prompt = Some(Prompt {
+                        kind: PromptKind::GotoLine,
+                        input: String::new(),
+                        error: None,
+                    });
+                }
+                Action::ToggleRelativeNumbers => {
+                    terminal_size = None;
+                    relative_numbers ^= true;
+                    status = if relative_numbers {
+                        "Relative line numbers"
+                    } else {
+                        "Absolute line numbers"
+                    }
+                    .to_string();
+                }
+                Action::ToggleVim => {
+                    terminal_size = None;
+                    vim = match vim {
+                        Some(_) => None,
+                        None => Some(Vim::default()),
+                    };
+                    status = if vim.is_some() {
+                        "Vim mode"
+                    } else {
+                        "Vim mode off"
+                    }
+                    .to_string();
+                }
+                Action::Search | Action::Replace => {
+                    terminal_size = None;
+                    editors[active_editor].start_search();
+                    let options = SearchOptions::default();
+                    prompt = Some(Prompt {
+                        kind: if action == Action::Search {
+                            PromptKind::Search(options)
+                        } else {
+                            PromptKind::Replace(options)
+                        },
+                        input: String::new(),
+                        error: None,
+                    });
+                }
+                Action::DebugScrollUp => {
                     terminal_size = None;
                     scroll = scroll.saturating_sub(5);
                 }
-                Event::Key(KeyEvent {
-                    code: KeyCode::PageDown,
-                    modifiers: KeyModifiers::ALT,
-                    ..
-                }) => {
+                Action::DebugScrollDown => {
                     terminal_size = None;
                     scroll += 5;
                 }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('d'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }) => {
+                Action::ToggleDebug => {
                     terminal_size = None;
                     debug ^= true;
                 }
-                _ => {
-                    if editors[active_editor].handle_event(event) {
+                action => {
+                    if editors[active_editor].apply(action) {
                         terminal_size = None;
                     }
                 }
@@ -226,3 +874,203 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
         }
     }
 }
+
+struct ScreenAreas {
+    main: Rect,
+    status: Rect,
+    debug: Rect,
+    // NOTE: the header and the pane of each editor, a header is as tall as its breadcrumb
+    editors: Vec<(Rect, Rect)>,
+}
+
+fn screen_areas(area: Rect, editors: &[Editor], debug: bool) -> ScreenAreas {
+    let constraints = std::iter::repeat_n(Constraint::Fill(1), editors.len()).collect::<Vec<_>>();
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Fill(1), Constraint::Length(1)])
+        .split(area);
+    let cols = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(43), Constraint::Fill(2)])
+        .split(rows[0]);
+    let editor_areas = if debug {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(&constraints)
+            .split(cols[0])
+    } else {
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(&constraints)
+            .split(rows[0])
+    };
+    ScreenAreas {
+        main: rows[0],
+        status: rows[1],
+        debug: cols[1],
+        editors: editors
+            .iter()
+            .zip(editor_areas.iter())
+            .map(|(editor, area)| {
+                let [header, pane] = Layout::vertical([
+                    Constraint::Length(editor.location().lines.len() as _),
+                    Constraint::Percentage(100),
+                ])
+                .areas(*area);
+                (header, pane)
+            })
+            .collect(),
+    }
+}
+
+// NOTE: room for the numbers of all the lines and a space, the width changes with the text
+fn gutter_width(editor: &Editor) -> u16 {
+    editor.line_count().to_string().len().max(3) as u16 + 1
+}
+
+fn text_spans<'r>(
+    slice: ropey::RopeSlice<'r>,
+    selected: Option<Range<usize>>,
+    highlights: &[Range<usize>],
+    tokens: &[(Range<usize>, TokenKind)],
+    cursors: &[Range<usize>],
+    theme: &Theme,
+) -> Vec<Span<'r>> {
+    let len_chars = slice.len_chars();
+    let mut bounds = vec![0, len_chars];
+    for range in selected
+        .iter()
+        .chain(highlights)
+        .chain(tokens.iter().map(|(range, _)| range))
+        .chain(cursors)
+    {
+        bounds.extend([range.start, range.end]);
+    }
+    bounds.sort_unstable();
+    bounds.dedup();
+    bounds
+        .windows(2)
+        .map(|bound| {
+            let (start, end) = (bound[0], bound[1]);
+            let mut part = slice.slice(start..end);
+            // NOTE: a selected newline, or one under a cursor, is shown as a space
+            if part.chars().last() == Some('\n') {
+                part = part.slice(..part.len_chars() - 1);
+                if part.len_chars() == 0 {
+                    part = " ".into();
+                }
+            }
+            let mut span = Span::raw(part);
+            if let Some((_, kind)) = tokens.iter().find(|(range, _)| range.contains(&start)) {
+                span = span.patch_style(theme.token(*kind));
+            }
+            if highlights.iter().any(|range| range.contains(&start)) {
+                span = span.patch_style(theme.search_match);
+            }
+            if selected
+                .as_ref()
+                .is_some_and(|range| range.contains(&start))
+            {
+                span = span.patch_style(theme.selection);
+            }
+            if cursors.iter().any(|range| range.contains(&start)) {
+                span = span.patch_style(theme.cursors);
+            }
+            span
+        })
+        .collect()
+}
+
+fn copy_to_clipboard<B: Backend + io::Write>(
+    clipboard: &mut Option<Box<dyn clipboard::Clipboard>>,
+    kill_ring: &KillRing,
+    terminal: &mut Terminal<B>,
+) -> String {
+    let (Some(clipboard), Some(text)) = (clipboard, kill_ring.get(0)) else {
+        return String::new();
+    };
+    match clipboard.copy(text, terminal.backend_mut()) {
+        Ok(()) => String::new(),
+        Err(err) => format!("Could not copy to the clipboard: {err}"),
+    }
+}
+
+enum PromptKind {
+    Travel,
+    GotoLine,
+    Search(SearchOptions),
+    Replace(SearchOptions),
+    ReplaceWith(SearchOptions, String),
+    ReplaceConfirm,
+}
+
+impl PromptKind {
+    fn label(&self) -> String {
+        match self {
+            Self::Travel => {
+                "Go back in time by (e.g. 30s, 10m, 2h, -5m to go forward): ".to_string()
+            }
+            Self::GotoLine => "Go to line (+N/-N relative): ".to_string(),
+            Self::Search(options) => format!("Search {}: ", search_flags(options)),
+            Self::Replace(options) => format!("Replace {}: ", search_flags(options)),
+            Self::ReplaceWith(options, pattern) => {
+                if options.regex {
+                    format!("Replace {pattern:?} with ($1 for groups, Ctrl-A all): ")
+                } else {
+                    format!("Replace {pattern:?} with (Ctrl-A all): ")
+                }
+            }
+            Self::ReplaceConfirm => String::new(),
+        }
+    }
+}
+
+fn search_flags(options: &SearchOptions) -> String {
+    let flags = [
+        (options.regex, "regex"),
+        (options.case_insensitive, "ignore case"),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, flag)| enabled.then_some(flag))
+    .collect::<Vec<_>>();
+    if flags.is_empty() {
+        "(Alt-R regex, Alt-C ignore case)".to_string()
+    } else {
+        format!("({})", flags.join(", "))
+    }
+}
+
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+    error: Option<String>,
+}
+
+// NOTE: the lines are numbered from 1 on the screen and from 0 in the editor
+fn parse_line(input: &str, current: usize) -> Option<usize> {
+    let input = input.trim();
+    let input = input.strip_prefix(':').unwrap_or(input);
+    if let Some(lines) = input.strip_prefix('+') {
+        current.checked_add(lines.trim().parse().ok()?)
+    } else if let Some(lines) = input.strip_prefix('-') {
+        current.checked_sub(lines.trim().parse().ok()?)
+    } else {
+        input.parse::<usize>().ok()?.checked_sub(1)
+    }
+}
+
+fn parse_duration(input: &str) -> Option<i64> {
+    let input = input.trim();
+    let (number, unit) = match input.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => input.split_at(i),
+        None => (input, "s"),
+    };
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+    number.trim().parse::<i64>().ok()?.checked_mul(unit)
+}
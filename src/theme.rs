@@ -0,0 +1,392 @@
+use ratatui::style::{Color, Modifier, Style};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::editor::TokenKind;
+use crate::file::config_dir;
+
+// NOTE: the built-in themes are written in the same format as the theme files, a file starts
+//       from one of them with `extends = "name"` at the top, "dark" when it says nothing
+static DARK: &str = r##"
+[header]
+fg = "white"
+bg = "blue"
+
+[active]
+fg = "white"
+
+[inactive]
+fg = "gray"
+
+[gutter]
+fg = "darkgray"
+
+[line_number]
+fg = "white"
+
+[continuation]
+fg = "darkgray"
+
+[selection]
+reversed = true
+
+[cursors]
+reversed = true
+
+[search_match]
+fg = "black"
+bg = "yellow"
+
+[hint]
+fg = "darkgray"
+
+[syntax.keyword]
+fg = "magenta"
+
+[syntax.string]
+fg = "green"
+
+[syntax.comment]
+fg = "darkgray"
+
+[syntax.lifetime]
+fg = "cyan"
+
+[syntax.number]
+fg = "yellow"
+
+[syntax.macro]
+fg = "blue"
+"##;
+
+static LIGHT: &str = r##"
+[header]
+fg = "white"
+bg = "blue"
+
+[active]
+fg = "black"
+
+[inactive]
+fg = "darkgray"
+
+[gutter]
+fg = "gray"
+
+[line_number]
+fg = "black"
+bold = true
+
+[continuation]
+fg = "gray"
+
+[selection]
+reversed = true
+
+[cursors]
+reversed = true
+
+[search_match]
+fg = "black"
+bg = "lightyellow"
+
+[hint]
+fg = "gray"
+
+[syntax.keyword]
+fg = "magenta"
+
+[syntax.string]
+fg = "#007000"
+
+[syntax.comment]
+fg = "gray"
+italic = true
+
+[syntax.lifetime]
+fg = "#006070"
+
+[syntax.number]
+fg = "red"
+
+[syntax.macro]
+fg = "blue"
+"##;
+
+static HIGH_CONTRAST: &str = r##"
+[header]
+fg = "black"
+bg = "white"
+bold = true
+
+[active]
+fg = "white"
+
+[inactive]
+fg = "white"
+dim = true
+
+[gutter]
+fg = "white"
+
+[line_number]
+fg = "black"
+bg = "yellow"
+bold = true
+
+[continuation]
+bg = "darkgray"
+
+[selection]
+fg = "black"
+bg = "lightcyan"
+
+[cursors]
+fg = "black"
+bg = "white"
+
+[search_match]
+fg = "black"
+bg = "lightyellow"
+bold = true
+
+[hint]
+fg = "white"
+
+[syntax.keyword]
+fg = "lightmagenta"
+bold = true
+
+[syntax.string]
+fg = "lightgreen"
+
+[syntax.comment]
+fg = "lightblue"
+italic = true
+
+[syntax.lifetime]
+fg = "lightcyan"
+
+[syntax.number]
+fg = "lightyellow"
+
+[syntax.macro]
+fg = "lightred"
+bold = true
+"##;
+
+pub static BUILTIN: &[&str] = &["dark", "light", "high-contrast"];
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub header: Style,
+    pub active: Style,
+    pub inactive: Style,
+    pub gutter: Style,
+    pub line_number: Style,
+    pub continuation: Style,
+    pub selection: Style,
+    pub cursors: Style,
+    pub search_match: Style,
+    pub hint: Style,
+    pub keyword: Style,
+    pub string: Style,
+    pub comment: Style,
+    pub lifetime: Style,
+    pub number: Style,
+    pub macro_call: Style,
+}
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let text = match name {
+            "dark" => DARK,
+            "light" => LIGHT,
+            "high-contrast" => HIGH_CONTRAST,
+            _ => return None,
+        };
+        Some(Self::default().patched(text).unwrap())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut base = "dark";
+        for (i, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.starts_with('[') {
+                break;
+            }
+            if let Some(("extends", value)) = split_key(line) {
+                base = parse_string(value)
+                    .ok_or_else(|| format!("line {}: expected a string", i + 1))?;
+            }
+        }
+        let theme = Self::builtin(base)
+            .ok_or_else(|| format!("unknown theme: {base} (expected {})", BUILTIN.join(", ")))?;
+        theme.patched(text)
+    }
+
+    pub fn token(&self, kind: TokenKind) -> Style {
+        match kind {
+            TokenKind::Keyword => self.keyword,
+            TokenKind::String => self.string,
+            TokenKind::Comment => self.comment,
+            TokenKind::Lifetime => self.lifetime,
+            TokenKind::Number => self.number,
+            TokenKind::Macro => self.macro_call,
+        }
+    }
+
+    fn style_mut(&mut self, section: &str) -> Option<&mut Style> {
+        Some(match section {
+            "header" => &mut self.header,
+            "active" => &mut self.active,
+            "inactive" => &mut self.inactive,
+            "gutter" => &mut self.gutter,
+            "line_number" => &mut self.line_number,
+            "continuation" => &mut self.continuation,
+            "selection" => &mut self.selection,
+            "cursors" => &mut self.cursors,
+            "search_match" => &mut self.search_match,
+            "hint" => &mut self.hint,
+            "syntax.keyword" => &mut self.keyword,
+            "syntax.string" => &mut self.string,
+            "syntax.comment" => &mut self.comment,
+            "syntax.lifetime" => &mut self.lifetime,
+            "syntax.number" => &mut self.number,
+            "syntax.macro" => &mut self.macro_call,
+            _ => return None,
+        })
+    }
+
+    // NOTE: the keys of a section change only what they name in the style of the base theme
+    fn patched(mut self, text: &str) -> Result<Self, String> {
+        let mut section: Option<String> = None;
+        for (i, line) in text.lines().enumerate() {
+            let error = |message: String| format!("line {}: {message}", i + 1);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name
+                    .strip_suffix(']')
+                    .ok_or_else(|| error("expected ]".to_string()))?
+                    .trim();
+                if self.style_mut(name).is_none() {
+                    return Err(error(format!("unknown section: {name}")));
+                }
+                section = Some(name.to_string());
+                continue;
+            }
+            let (key, value) =
+                split_key(line).ok_or_else(|| error("expected key = value".to_string()))?;
+            let Some(section) = &section else {
+                if key == "extends" {
+                    continue;
+                }
+                return Err(error(format!("unknown key: {key}")));
+            };
+            let style = self.style_mut(section).unwrap();
+            match key {
+                "fg" | "bg" => {
+                    let color = parse_string(value)
+                        .and_then(|value| value.parse::<Color>().ok())
+                        .ok_or_else(|| error(format!("invalid color: {value}")))?;
+                    *style = if key == "fg" {
+                        style.fg(color)
+                    } else {
+                        style.bg(color)
+                    };
+                }
+                _ => {
+                    let modifier = match key {
+                        "bold" => Modifier::BOLD,
+                        "dim" => Modifier::DIM,
+                        "italic" => Modifier::ITALIC,
+                        "underlined" => Modifier::UNDERLINED,
+                        "reversed" => Modifier::REVERSED,
+                        _ => return Err(error(format!("unknown key: {key}"))),
+                    };
+                    *style = match value {
+                        "true" => style.add_modifier(modifier),
+                        "false" => style.remove_modifier(modifier),
+                        _ => return Err(error(format!("expected true or false: {value}"))),
+                    };
+                }
+            }
+        }
+        Ok(self)
+    }
+}
+
+// NOTE: a # inside a string is a color, not a comment
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once('=')?;
+    Some((key.trim(), value.trim()))
+}
+
+fn parse_string(value: &str) -> Option<&str> {
+    value.strip_prefix('"')?.strip_suffix('"')
+}
+
+// NOTE: the file is read again whenever its modification time changes
+#[derive(Debug)]
+pub struct ThemeFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ThemeFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            modified: None,
+        }
+    }
+
+    pub fn changed(&self) -> bool {
+        self.modified() != self.modified
+    }
+
+    pub fn load(&mut self) -> Result<Theme, String> {
+        self.modified = self.modified();
+        let text = std::fs::read_to_string(&self.path)
+            .map_err(|err| format!("{}: {err}", self.path.display()))?;
+        Theme::parse(&text).map_err(|err| format!("{}: {err}", self.path.display()))
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.path).ok()?.modified().ok()
+    }
+}
+
+// NOTE: JAFFACODE_THEME is the name of a built-in theme or the path of a theme file, without it
+//       the theme file in the config directory is used when there is one
+pub fn from_env() -> (Theme, Option<ThemeFile>) {
+    let dark = Theme::builtin("dark").unwrap();
+    match std::env::var_os("JAFFACODE_THEME") {
+        Some(value) => match value.to_str().and_then(Theme::builtin) {
+            Some(theme) => (theme, None),
+            None => (dark, Some(ThemeFile::new(value.into()))),
+        },
+        None => {
+            let file = config_dir()
+                .map(|dir| dir.join("theme.toml"))
+                .filter(|path| path.is_file())
+                .map(ThemeFile::new);
+            (dark, file)
+        }
+    }
+}